@@ -1,10 +1,281 @@
+from typing import Any, Awaitable, Callable, Dict, Optional, Tuple, Union
+
 class ImageHash:
     bool_values: list[bool]
     values: list[int]
     hash_size: int
+    width: int
+    height: int
+    uncertain: list[bool]
+    algorithm: Optional[str]
+    byte_len: int
 
+    def __init__(self, bool_values: list[bool], hash_size: int) -> None: ...
+    def __len__(self) -> int: ...
+    def to_hex(self) -> str: ...
+    def to_int(self) -> int: ...
+    @staticmethod
+    def from_int(value: int, hash_size: int) -> ImageHash: ...
+    def to_csv_row(self, path: Optional[str] = None) -> str: ...
     def distance(self, other: ImageHash) -> int: ...
+    def distance_hex(self, other_hex: str) -> int: ...
+    def distance_bounded(self, other: ImageHash, max: int) -> Optional[int]: ...
+    def certain_distance(self, other: ImageHash) -> int: ...
+    def masked_distance(self, other: ImageHash, mask: list[bool]) -> int: ...
+    def weighted_distance(self, other: ImageHash, weights: list[float]) -> float: ...
+    def distance_either_order(self, other: ImageHash) -> int: ...
+    def distance_rescaled(self, other: ImageHash) -> float: ...
+    def min_distance_to(self, references: list[ImageHash]) -> int: ...
+    def distance_histogram(self, gallery: list[ImageHash], bins: int) -> list[int]: ...
+    def rotate90(self, times: int) -> ImageHash: ...
+    def likely_transformed(
+        self, other: ImageHash, max_distance: Optional[int] = None
+    ) -> bool: ...
+    def downsample(self, factor: int) -> ImageHash: ...
+    def lsh_bucket(self, planes: int, seed: int) -> int: ...
+    def match_probability(
+        self, other: ImageHash, algo_calibration: Optional[str] = None
+    ) -> float: ...
+    def entropy(self) -> float: ...
+
+class Comparison:
+    distance: Optional[int]
+    normalized_distance: Optional[float]
+    similarity: Optional[float]
+    are_comparable: bool
+
+class Timings:
+    decode_ms: float
+    resize_ms: float
+    transform_ms: float
+
+class TiledHasher:
+    def __init__(
+        self, width: int, height: int, max_side: Optional[int] = None
+    ) -> None: ...
+    def feed(self, tile_bytes: bytes, x: int, y: int) -> None: ...
+    def finalize(self, hash_size: int, algo: str) -> ImageHash: ...
+
+class HashConfig:
+    algo: str
+    hash_size: int
+
+    def __init__(
+        self,
+        algo: str,
+        hash_size: int,
+        highfreq_factor: Optional[int] = None,
+        background: Optional[Tuple[int, int, int]] = None,
+        filter: Optional[str] = None,
+        equalize: Optional[bool] = None,
+        crop_to_square: Optional[bool] = None,
+        pad_mode: Optional[str] = None,
+        linearize_srgb: Optional[bool] = None,
+        channel: Optional[str] = None,
+        clahe: Optional[Tuple[int, float]] = None,
+        drop_dc: Optional[bool] = None,
+        backend: Optional[str] = None,
+        center: Optional[bool] = None,
+        keep: Optional[int] = None,
+        center_weight: Optional[float] = None,
+        parallel: Optional[bool] = None,
+        blur: Optional[float] = None,
+        snap_to_grid: Optional[int] = None,
+        unpremultiply: Optional[bool] = None,
+        uncertain_epsilon: Optional[float] = None,
+        auto_deskew: Optional[bool] = None,
+        remove_matte: Optional[bool] = None,
+        matte_tolerance: Optional[int] = None,
+        saliency_crop: Optional[bool] = None,
+    ) -> None: ...
+
+class Hasher:
+    config: HashConfig
+
+    def __init__(self, config: HashConfig) -> None: ...
+    def hash(self, fpath: str) -> ImageHash: ...
 
-def ahash(fpath: str, hash_size: int) -> ImageHash: ...
-def dhash(fpath: str, hash_size: int) -> ImageHash: ...
-def phash(fpath: str, hash_size: int, highfreq_factor: int) -> ImageHash: ...
+def ahash(
+    fpath: str,
+    hash_size: Union[int, Tuple[int, int]],
+    background: Optional[Tuple[int, int, int]] = None,
+    center: Optional[bool] = None,
+    filter: Optional[str] = None,
+    equalize: Optional[bool] = None,
+    crop_to_square: Optional[bool] = None,
+    strict: Optional[bool] = None,
+    linearize_srgb: Optional[bool] = None,
+    unpremultiply: Optional[bool] = None,
+    uncertain_epsilon: Optional[float] = None,
+    channel: Optional[str] = None,
+    clahe: Optional[Tuple[int, float]] = None,
+    page: Optional[int] = None,
+    profile: Optional[bool] = None,
+    min_entropy: Optional[float] = None,
+    auto_deskew: Optional[bool] = None,
+    pad_mode: Optional[str] = None,
+    remove_matte: Optional[bool] = None,
+    matte_tolerance: Optional[int] = None,
+    saliency_crop: Optional[bool] = None,
+) -> Union[ImageHash, Tuple[ImageHash, Timings]]: ...
+def ahash_async(fpath: str, hash_size: int) -> Awaitable[ImageHash]: ...
+def ahash_custom(
+    fpath: str, hash_size: int, threshold_fn: Callable[[float, list[int]], float]
+) -> ImageHash: ...
+def ahash_from_grayscale_buffer(data: bytes, size: int) -> ImageHash: ...
+def ahash_from_matrix(matrix: list[list[int]], hash_size: int) -> ImageHash: ...
+def ahash_from_ndarray(arr: Any, hash_size: int, bgr: bool) -> ImageHash: ...
+def dhash(
+    fpath: str,
+    hash_size: int,
+    background: Optional[Tuple[int, int, int]] = None,
+    filter: Optional[str] = None,
+    equalize: Optional[bool] = None,
+    crop_to_square: Optional[bool] = None,
+    blur: Optional[float] = None,
+    strict: Optional[bool] = None,
+    linearize_srgb: Optional[bool] = None,
+    unpremultiply: Optional[bool] = None,
+    uncertain_epsilon: Optional[float] = None,
+    snap_to_grid: Optional[int] = None,
+    channel: Optional[str] = None,
+    clahe: Optional[Tuple[int, float]] = None,
+    page: Optional[int] = None,
+    profile: Optional[bool] = None,
+    min_entropy: Optional[float] = None,
+    auto_deskew: Optional[bool] = None,
+    pad_mode: Optional[str] = None,
+    remove_matte: Optional[bool] = None,
+    matte_tolerance: Optional[int] = None,
+    saliency_crop: Optional[bool] = None,
+) -> Union[ImageHash, Tuple[ImageHash, Timings]]: ...
+def dhash_pair(fpath: str, hash_size: int) -> Tuple[ImageHash, ImageHash]: ...
+def grayscale_array(fpath: str, hash_size: int, algo: str) -> Any: ...
+def phash(
+    fpath: str,
+    hash_size: int,
+    highfreq_factor: int,
+    background: Optional[Tuple[int, int, int]] = None,
+    filter: Optional[str] = None,
+    drop_dc: Optional[bool] = None,
+    equalize: Optional[bool] = None,
+    crop_to_square: Optional[bool] = None,
+    backend: Optional[str] = None,
+    center_weight: Optional[float] = None,
+    parallel: Optional[bool] = None,
+    keep: Optional[int] = None,
+    strict: Optional[bool] = None,
+    linearize_srgb: Optional[bool] = None,
+    unpremultiply: Optional[bool] = None,
+    channel: Optional[str] = None,
+    clahe: Optional[Tuple[int, float]] = None,
+    page: Optional[int] = None,
+    profile: Optional[bool] = None,
+    min_entropy: Optional[float] = None,
+    auto_deskew: Optional[bool] = None,
+    pad_mode: Optional[str] = None,
+    remove_matte: Optional[bool] = None,
+    matte_tolerance: Optional[int] = None,
+    intermediate_filter: Optional[str] = None,
+    saliency_crop: Optional[bool] = None,
+) -> Union[ImageHash, Tuple[ImageHash, Timings]]: ...
+def phash_debug(
+    fpath: str, hash_size: int, highfreq_factor: int
+) -> Tuple[ImageHash, list[Tuple[int, int]]]: ...
+def phash_spectral(fpath: str, hash_size: int) -> ImageHash: ...
+def mhash(
+    fpath: str,
+    hash_size: int,
+    background: Optional[Tuple[int, int, int]] = None,
+    filter: Optional[str] = None,
+    strict: Optional[bool] = None,
+    linearize_srgb: Optional[bool] = None,
+    unpremultiply: Optional[bool] = None,
+    channel: Optional[str] = None,
+    page: Optional[int] = None,
+    profile: Optional[bool] = None,
+    min_entropy: Optional[float] = None,
+    auto_deskew: Optional[bool] = None,
+    pad_mode: Optional[str] = None,
+    remove_matte: Optional[bool] = None,
+    matte_tolerance: Optional[int] = None,
+    saliency_crop: Optional[bool] = None,
+) -> Union[ImageHash, Tuple[ImageHash, Timings]]: ...
+def text_robust_hash(fpath: str, hash_size: int) -> ImageHash: ...
+def quad_hash(fpath: str, hash_size: int) -> ImageHash: ...
+def quad_distance(a: ImageHash, b: ImageHash) -> int: ...
+def alpha_hash(fpath: str, hash_size: int) -> ImageHash: ...
+def thumbhash(fpath: str) -> list[int]: ...
+def ahash_base64(data_url: str, hash_size: int) -> ImageHash: ...
+def dhash_base64(data_url: str, hash_size: int) -> ImageHash: ...
+def phash_base64(data_url: str, hash_size: int) -> ImageHash: ...
+def mhash_base64(data_url: str, hash_size: int) -> ImageHash: ...
+def ahash_raw_bytes(fpath: str, hash_size: int) -> bytes: ...
+def dhash_raw_bytes(fpath: str, hash_size: int) -> bytes: ...
+def phash_raw_bytes(fpath: str, hash_size: int) -> bytes: ...
+def mhash_raw_bytes(fpath: str, hash_size: int) -> bytes: ...
+def hash_video_frame(
+    fpath: str, hash_size: int, algo: str, timestamp_sec: float
+) -> ImageHash: ...
+def hash_with_thumbnail(
+    fpath: str, hash_size: int, thumb_size: int, algo: str
+) -> Tuple[ImageHash, list[int]]: ...
+def hash_pyramid(
+    fpath: str, levels: int, base_size: int, algo: str
+) -> list[ImageHash]: ...
+def hash_frames(fpath: str, hash_size: int, algo: str) -> list[ImageHash]: ...
+def frame_delta_hashes(fpath: str, hash_size: int) -> list[int]: ...
+def hash_cached(
+    fpath: str, hash_size: int, algo: str, cache_path: str
+) -> ImageHash: ...
+def hash_archive(
+    archive_path: str, hash_size: int, algo: str
+) -> list[Tuple[str, Optional[ImageHash]]]: ...
+def find_duplicates(
+    paths: list[str],
+    hash_size: int,
+    algo: str,
+    threshold: int,
+    max_inflight: Optional[int] = None,
+) -> list[list[str]]: ...
+def diversity_report(dir: str, hash_size: int, algo: str) -> float: ...
+def assignment_distance(a: list[ImageHash], b: list[ImageHash]) -> int: ...
+def merge_galleries(
+    existing: list[ImageHash], incoming: list[ImageHash], threshold: int
+) -> list[int]: ...
+def robustness_report(fpath: str, hash_size: int) -> Dict[str, int]: ...
+def bit_error_profile(fpath: str, hash_size: int, algo: str) -> list[float]: ...
+def self_check() -> str: ...
+def collision_probability(hash_bits: int, threshold: int) -> float: ...
+def bit_frequency(hashes: list[ImageHash]) -> list[int]: ...
+def learn_bit_weights(pairs: list[Tuple[ImageHash, ImageHash, bool]]) -> list[float]: ...
+def centroid(hashes: list[ImageHash]) -> ImageHash: ...
+def strip_hash(fpath: str, strips: int, hash_size: int) -> list[ImageHash]: ...
+def strip_distance(a: list[ImageHash], b: list[ImageHash]) -> int: ...
+def aligned_phash(
+    path_a: str, path_b: str, hash_size: int, highfreq_factor: int
+) -> int: ...
+def compare_files(path_a: str, path_b: str, hash_size: int, algo: str) -> int: ...
+def compare_bytes(a: bytes, b: bytes, hash_size: int, algo: str) -> int: ...
+def classify_pair(path_a: str, path_b: str, hash_size: int, threshold: int) -> str: ...
+def signature(fpath: str, hash_size: int, algo: str) -> Tuple[ImageHash, float]: ...
+def signature_distance(
+    hash_a: ImageHash,
+    aspect_a: float,
+    hash_b: ImageHash,
+    aspect_b: float,
+    aspect_weight: Optional[float] = None,
+) -> float: ...
+def ensemble_distance(
+    path_a: str,
+    path_b: str,
+    hash_size: int,
+    weights: Optional[Tuple[float, float, float]] = None,
+) -> float: ...
+def compare_detailed(path_a: str, path_b: str, hash_size: int) -> Dict[str, float]: ...
+def compare(a: ImageHash, b: ImageHash) -> Comparison: ...
+def render_diff(a: ImageHash, b: ImageHash, out_path: str, scale: int) -> None: ...
+def save_hashes(hashes: list[ImageHash], path: str) -> None: ...
+def load_hashes(path: str) -> list[ImageHash]: ...
+def load_csv(file: str) -> list[Tuple[str, ImageHash]]: ...
+def packed_len(hash_size: int) -> int: ...
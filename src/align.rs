@@ -0,0 +1,141 @@
+// Translation estimate between two images by phase correlation.
+//
+// Both images are reduced to a small square grayscale grid, windowed to soften their
+// borders and transformed with a plain DFT. The normalized cross-power spectrum of the
+// two transforms is a pure phase ramp for a translated copy, and its inverse peaks at
+// the shift.
+
+use std::f64::consts::PI;
+
+use image::{imageops::FilterType, DynamicImage};
+
+// Side of the grid the correlation runs at. Shifts are resolved to one cell of it.
+const GRID: usize = 128;
+
+pub(crate) type Complex = (f64, f64);
+
+// Shift `(dx, dy)`, as a fraction of the image size, such that the content at `(x, y)`
+// in `a` is found at `(x + dx, y + dy)` in `b`.
+pub(crate) fn estimate_shift(a: &DynamicImage, b: &DynamicImage) -> (f64, f64) {
+    let fa = dft2(&windowed(a), GRID, false);
+    let fb = dft2(&windowed(b), GRID, false);
+
+    let cross: Vec<Complex> = fa
+        .iter()
+        .zip(&fb)
+        .map(|(p, q)| {
+            // conj(p) * q, normalized to unit magnitude.
+            let re = p.0 * q.0 + p.1 * q.1;
+            let im = p.0 * q.1 - p.1 * q.0;
+            let norm = (re * re + im * im).sqrt();
+            if norm > 0.0 {
+                (re / norm, im / norm)
+            } else {
+                (0.0, 0.0)
+            }
+        })
+        .collect();
+    let correlation = dft2(&cross, GRID, true);
+
+    let mut peak = 0;
+    for (i, value) in correlation.iter().enumerate() {
+        if value.0 > correlation[peak].0 {
+            peak = i;
+        }
+    }
+
+    // Peaks past the middle wrap around to negative shifts.
+    let unwrap = |v: usize| {
+        let v = v as f64;
+        if v > GRID as f64 / 2.0 {
+            v - GRID as f64
+        } else {
+            v
+        }
+    };
+    (
+        unwrap(peak % GRID) / GRID as f64,
+        unwrap(peak / GRID) / GRID as f64,
+    )
+}
+
+// Crops `a` and `b` to the part of the scene they share, given the shift found by
+// `estimate_shift`.
+pub(crate) fn crop_overlap(
+    a: DynamicImage,
+    b: DynamicImage,
+    (dx, dy): (f64, f64),
+) -> (DynamicImage, DynamicImage) {
+    // Offset and size of the overlap within an image, along one axis where the content
+    // moved by `shift` pixels from `a` to `b`.
+    let span = |len: u32, shift: i64, in_b: bool| -> (u32, u32) {
+        let shift = shift.clamp(-(len as i64 - 1), len as i64 - 1);
+        let start = if (shift > 0) == in_b {
+            shift.unsigned_abs()
+        } else {
+            0
+        };
+        (start as u32, len - shift.unsigned_abs() as u32)
+    };
+    let crop = |img: DynamicImage, in_b: bool| {
+        let (width, height) = (img.width(), img.height());
+        let (x, w) = span(width, (dx * width as f64).round() as i64, in_b);
+        let (y, h) = span(height, (dy * height as f64).round() as i64, in_b);
+        img.crop_imm(x, y, w, h)
+    };
+    (crop(a, false), crop(b, true))
+}
+
+// Grayscale `GRID` x `GRID` reduction of an image, mean removed and Hann windowed.
+fn windowed(img: &DynamicImage) -> Vec<Complex> {
+    let gray = img
+        .resize_exact(GRID as u32, GRID as u32, FilterType::Triangle)
+        .to_luma8();
+    let mean = gray.pixels().map(|px| px.0[0] as f64).sum::<f64>() / (GRID * GRID) as f64;
+    let hann: Vec<f64> = (0..GRID)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / (GRID - 1) as f64).cos())
+        .collect();
+
+    gray.enumerate_pixels()
+        .map(|(x, y, px)| {
+            let weight = hann[x as usize] * hann[y as usize];
+            ((px.0[0] as f64 - mean) * weight, 0.0)
+        })
+        .collect()
+}
+
+// 2D DFT of a row-major `size` x `size` grid, as a DFT of every row then every column.
+// The inverse transform is left unscaled, callers only compare its values to each other.
+pub(crate) fn dft2(grid: &[Complex], size: usize, inverse: bool) -> Vec<Complex> {
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let twiddles: Vec<Complex> = (0..size)
+        .map(|k| {
+            let angle = sign * 2.0 * PI * k as f64 / size as f64;
+            (angle.cos(), angle.sin())
+        })
+        .collect();
+
+    let dft = |input: &[Complex]| -> Vec<Complex> {
+        (0..size)
+            .map(|k| {
+                let mut sum = (0.0, 0.0);
+                for (n, v) in input.iter().enumerate() {
+                    let t = twiddles[k * n % size];
+                    sum.0 += v.0 * t.0 - v.1 * t.1;
+                    sum.1 += v.0 * t.1 + v.1 * t.0;
+                }
+                sum
+            })
+            .collect()
+    };
+
+    let rows: Vec<Complex> = grid.chunks(size).flat_map(dft).collect();
+    let mut out = vec![(0.0, 0.0); size * size];
+    for x in 0..size {
+        let column: Vec<Complex> = (0..size).map(|y| rows[y * size + x]).collect();
+        for (y, v) in dft(&column).into_iter().enumerate() {
+            out[y * size + x] = v;
+        }
+    }
+    out
+}
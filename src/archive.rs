@@ -0,0 +1,334 @@
+// Hashing of the images stored in a zip or tar archive, without extracting it.
+//
+// Entries are read one at a time and decoded from memory, so only one of them is held at
+// once. Zip archives may use the stored or deflate methods, tar archives may be gzipped,
+// and their GNU long names and pax paths are honored. Zip64 archives, for more than 65535
+// entries or 4 GiB, are read too. Entries that are not images are skipped, while an image
+// that cannot be decoded is reported with no hash. Without the `archive` feature every
+// call fails with a `PyValueError`.
+//
+// Sizes read from an archive are never trusted for allocations: buffers only grow as
+// data is actually read, and a zip directory or entry that claims more than the file
+// holds, or more than deflate can expand to, is rejected.
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{Algorithm, ImageHash};
+
+#[cfg(feature = "archive")]
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+};
+
+#[cfg(feature = "archive")]
+use flate2::read::{DeflateDecoder, GzDecoder};
+
+#[cfg(feature = "archive")]
+use crate::HashOptions;
+
+// `(entry name, hash)` of every image in the archive at `archive_path`, in archive order.
+#[cfg(feature = "archive")]
+pub(crate) fn hash_archive(
+    archive_path: &str,
+    hash_size: u32,
+    algo: Algorithm,
+) -> PyResult<Vec<(String, Option<ImageHash>)>> {
+    let mut file = match File::open(archive_path) {
+        Ok(file) => file,
+        Err(_e) => return Err(PyValueError::new_err("Cannot open archive.")),
+    };
+    let mut magic = [0u8; 4];
+    let read = file.read(&mut magic).unwrap_or(0);
+    if file.rewind().is_err() {
+        return Err(PyValueError::new_err("Cannot read archive."));
+    }
+
+    let mut hashes = Vec::new();
+    let mut visit = |name: String, data: Option<Vec<u8>>| {
+        let data = match data {
+            Some(data) => data,
+            None => return hashes.push((name, None)),
+        };
+        if image::guess_format(&data).is_err() {
+            return;
+        }
+        let hash = image::load_from_memory(&data).ok().and_then(|img| {
+            algo.hash_image(img, hash_size, &HashOptions::default())
+                .ok()
+        });
+        hashes.push((name, hash));
+    };
+
+    let result = match magic[..read] {
+        [b'P', b'K', 3, 4] | [b'P', b'K', 5, 6] => read_zip(file, &mut visit),
+        [0x1f, 0x8b, ..] => read_tar(GzDecoder::new(BufReader::new(file)), &mut visit),
+        _ => read_tar(BufReader::new(file), &mut visit),
+    };
+    match result {
+        Some(()) => Ok(hashes),
+        None => Err(PyValueError::new_err("Invalid or unsupported archive.")),
+    }
+}
+
+#[cfg(not(feature = "archive"))]
+pub(crate) fn hash_archive(
+    _archive_path: &str,
+    _hash_size: u32,
+    _algo: Algorithm,
+) -> PyResult<Vec<(String, Option<ImageHash>)>> {
+    Err(PyValueError::new_err(
+        "Archive hashing is not enabled, build with the `archive` feature.",
+    ))
+}
+
+#[cfg(feature = "archive")]
+fn le16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+#[cfg(feature = "archive")]
+fn le32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+#[cfg(feature = "archive")]
+fn le64(bytes: &[u8], at: usize) -> u64 {
+    le32(bytes, at) as u64 | (le32(bytes, at + 4) as u64) << 32
+}
+
+// Deflate expands its input at most about 1032 times, which bounds the size an entry can
+// honestly declare.
+#[cfg(feature = "archive")]
+const MAX_DEFLATE_RATIO: u64 = 1032;
+
+// Value of a 16 or 32-bit zip field whose real value is in the Zip64 extra field.
+#[cfg(feature = "archive")]
+const ZIP64_MARKER: u32 = u32::MAX;
+
+// Reads exactly `len` bytes at `offset`, or None when the file does not hold them.
+#[cfg(feature = "archive")]
+fn read_at(file: &mut File, file_len: u64, offset: u64, len: u64) -> Option<Vec<u8>> {
+    if offset.checked_add(len)? > file_len {
+        return None;
+    }
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    let mut data = Vec::new();
+    file.take(len).read_to_end(&mut data).ok()?;
+    (data.len() as u64 == len).then_some(data)
+}
+
+// `(entries, directory size, directory offset)` from the Zip64 end of central directory
+// record, located through the locator right before the classic record at `end`.
+#[cfg(feature = "archive")]
+fn read_zip64_end(
+    file: &mut File,
+    file_len: u64,
+    tail: &[u8],
+    end: usize,
+) -> Option<(u64, u64, u64)> {
+    let locator = end.checked_sub(20)?;
+    if tail[locator..locator + 4] != [b'P', b'K', 6, 7] {
+        return None;
+    }
+    let record = read_at(file, file_len, le64(tail, locator + 8), 56)?;
+    if record[..4] != [b'P', b'K', 6, 6] {
+        return None;
+    }
+    Some((le64(&record, 32), le64(&record, 40), le64(&record, 48)))
+}
+
+// Replaces the `ZIP64_MARKER` values among `(size, compressed size, header offset)` by
+// the ones of the Zip64 extended information extra field, which lists only those, in
+// that order.
+#[cfg(feature = "archive")]
+fn apply_zip64_extra(extra: &[u8], fields: &mut [u64; 3]) -> Option<()> {
+    let mut at = 0;
+    while at + 4 <= extra.len() {
+        let (tag, len) = (le16(extra, at), le16(extra, at + 2) as usize);
+        let data = extra.get(at + 4..at + 4 + len)?;
+        if tag == 1 {
+            let mut next = 0;
+            for field in fields.iter_mut().filter(|f| **f == ZIP64_MARKER as u64) {
+                *field = le64(data.get(next..next + 8)?, 0);
+                next += 8;
+            }
+            return Some(());
+        }
+        at += 4 + len;
+    }
+    // A marker without the extra field to resolve it is a corrupt entry.
+    fields
+        .iter()
+        .all(|f| *f != ZIP64_MARKER as u64)
+        .then_some(())
+}
+
+// Calls `visit` with the name and contents of every file of a zip archive, the contents
+// being None when the entry cannot be extracted. Returns None when the archive itself
+// cannot be read.
+#[cfg(feature = "archive")]
+fn read_zip<F>(mut file: File, visit: &mut F) -> Option<()>
+where
+    F: FnMut(String, Option<Vec<u8>>),
+{
+    // The end of central directory record sits in the last 22 bytes, or up to 64 KiB
+    // earlier when the archive has a comment.
+    let file_len = file.seek(SeekFrom::End(0)).ok()?;
+    let tail_len = file_len.min(22 + u16::MAX as u64);
+    let tail_start = file_len - tail_len;
+    let tail = read_at(&mut file, file_len, tail_start, tail_len)?;
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|at| tail[*at..*at + 4] == [b'P', b'K', 5, 6])?;
+    let mut entries = le16(&tail, end + 10) as u64;
+    let mut directory_size = le32(&tail, end + 12) as u64;
+    let mut directory_offset = le32(&tail, end + 16) as u64;
+    if entries == u16::MAX as u64
+        || directory_size == ZIP64_MARKER as u64
+        || directory_offset == ZIP64_MARKER as u64
+    {
+        (entries, directory_size, directory_offset) =
+            read_zip64_end(&mut file, file_len, &tail, end)?;
+    }
+    let directory = read_at(&mut file, file_len, directory_offset, directory_size)?;
+
+    let mut at = 0;
+    for _ in 0..entries {
+        if directory.len() < at + 46 || directory[at..at + 4] != [b'P', b'K', 1, 2] {
+            return None;
+        }
+        let method = le16(&directory, at + 10);
+        let name_len = le16(&directory, at + 28) as usize;
+        let extra_len = le16(&directory, at + 30) as usize;
+        let comment_len = le16(&directory, at + 32) as usize;
+        let mut fields = [
+            le32(&directory, at + 24) as u64,
+            le32(&directory, at + 20) as u64,
+            le32(&directory, at + 42) as u64,
+        ];
+        let name = directory.get(at + 46..at + 46 + name_len)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        let extra = directory.get(at + 46 + name_len..at + 46 + name_len + extra_len)?;
+        apply_zip64_extra(extra, &mut fields)?;
+        at += 46 + name_len + extra_len + comment_len;
+
+        if name.ends_with('/') {
+            continue;
+        }
+        let [size, compressed_size, header_offset] = fields;
+        let data = read_zip_entry(
+            &mut file,
+            file_len,
+            header_offset,
+            method,
+            compressed_size,
+            size,
+        );
+        visit(name, data);
+    }
+    Some(())
+}
+
+// Contents of the zip entry whose local header is at `header_offset`.
+#[cfg(feature = "archive")]
+fn read_zip_entry(
+    file: &mut File,
+    file_len: u64,
+    header_offset: u64,
+    method: u16,
+    compressed_size: u64,
+    size: u64,
+) -> Option<Vec<u8>> {
+    let header = read_at(file, file_len, header_offset, 30)?;
+    if header[..4] != [b'P', b'K', 3, 4] {
+        return None;
+    }
+    let data_offset = header_offset + 30 + le16(&header, 26) as u64 + le16(&header, 28) as u64;
+    if data_offset.checked_add(compressed_size)? > file_len {
+        return None;
+    }
+    file.seek(SeekFrom::Start(data_offset)).ok()?;
+
+    let mut compressed = file.take(compressed_size);
+    let mut data = Vec::new();
+    match method {
+        0 if size == compressed_size => compressed.read_to_end(&mut data).ok()?,
+        8 if size <= compressed_size.saturating_mul(MAX_DEFLATE_RATIO) => {
+            DeflateDecoder::new(compressed)
+                .take(size)
+                .read_to_end(&mut data)
+                .ok()?
+        }
+        _ => return None,
+    };
+    if data.len() as u64 != size {
+        return None;
+    }
+    Some(data)
+}
+
+// Calls `visit` with the name and contents of every regular file of a tar archive.
+// Returns None when the archive cannot be read.
+#[cfg(feature = "archive")]
+fn read_tar<R, F>(mut reader: R, visit: &mut F) -> Option<()>
+where
+    R: Read,
+    F: FnMut(String, Option<Vec<u8>>),
+{
+    // Name given by a GNU long name or pax header to the entry that follows it.
+    let mut next_name: Option<String> = None;
+    let mut header = [0u8; 512];
+    loop {
+        if reader.read_exact(&mut header).is_err() {
+            // Some writers omit the trailing zero blocks.
+            return Some(());
+        }
+        if header.iter().all(|b| *b == 0) {
+            return Some(());
+        }
+
+        let field = |range: std::ops::Range<usize>| {
+            let bytes = &header[range];
+            let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+        let size = u64::from_str_radix(field(124..136).trim(), 8).ok()?;
+        let kind = header[156];
+        let mut name = field(0..100);
+        if &header[257..262] == b"ustar" {
+            let prefix = field(345..500);
+            if !prefix.is_empty() {
+                name = format!("{}/{}", prefix, name);
+            }
+        }
+
+        let mut data = Vec::new();
+        (&mut reader).take(size).read_to_end(&mut data).ok()?;
+        if data.len() as u64 != size {
+            return None;
+        }
+        let padding = (512 - size % 512) % 512;
+        std::io::copy(&mut (&mut reader).take(padding), &mut std::io::sink()).ok()?;
+
+        match kind {
+            b'L' => {
+                let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+                next_name = Some(String::from_utf8_lossy(&data[..end]).into_owned());
+            }
+            b'x' => next_name = pax_path(&data).or(next_name),
+            b'0' | 0 => visit(next_name.take().unwrap_or(name), Some(data)),
+            _ => next_name = None,
+        }
+    }
+}
+
+// `path` record of a pax extended header, made of `<length> <key>=<value>\n` records.
+#[cfg(feature = "archive")]
+fn pax_path(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    text.lines()
+        .filter_map(|record| record.split_once(' '))
+        .find_map(|(_len, pair)| pair.strip_prefix("path="))
+        .map(str::to_string)
+}
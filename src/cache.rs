@@ -0,0 +1,99 @@
+// On-disk cache of file hashes, so unchanged files are not hashed again on every run.
+//
+// The cache is a CSV file with one `path,size,mtime,algorithm,hash_size,hex` row per
+// entry, the path canonicalized and the mtime in nanoseconds since the Unix epoch. A
+// stored hash is reused while the file's size and mtime match, otherwise it is
+// recomputed and its entry replaced. The whole file is rewritten on every miss, through a
+// temporary file so an interrupted run never leaves it half written. Without the `cache`
+// feature every call fails with a `PyValueError`.
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{Algorithm, ImageHash};
+
+#[cfg(feature = "cache")]
+use std::{fs, io::ErrorKind, time::UNIX_EPOCH};
+
+#[cfg(feature = "cache")]
+use crate::{csv_field, parse_csv};
+
+// Hashes `fpath` with `algo`, going through the cache at `cache_path`.
+#[cfg(feature = "cache")]
+pub(crate) fn hash_cached(
+    fpath: &str,
+    hash_size: u32,
+    algo: Algorithm,
+    cache_path: &str,
+) -> PyResult<ImageHash> {
+    let key = match file_key(fpath) {
+        Some((path, size, mtime)) => [
+            path,
+            size.to_string(),
+            mtime.to_string(),
+            algo.name().to_string(),
+            hash_size.to_string(),
+        ],
+        None => return Err(PyValueError::new_err("Cannot open image.")),
+    };
+
+    let mut rows = match fs::read_to_string(cache_path) {
+        Ok(text) => parse_csv(&text),
+        Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(_e) => return Err(PyValueError::new_err("Cannot read cache.")),
+    };
+
+    let (width, height) = algo.bit_grid(hash_size as usize);
+    let stored = rows
+        .iter()
+        .find(|row| row.len() == 6 && row[..5] == key)
+        .and_then(|row| ImageHash::from_hex(&row[5], width, height));
+    if let Some(hash) = stored {
+        return Ok(hash.with_algorithm(algo));
+    }
+
+    let hash = algo.hash(fpath, hash_size)?;
+
+    // Entries for older versions of the file are dropped.
+    rows.retain(|row| !(row.len() == 6 && row[0] == key[0] && row[3..5] == key[3..5]));
+    let mut row = key.to_vec();
+    row.push(hash.to_hex());
+    rows.push(row);
+
+    let text: String = rows
+        .iter()
+        .map(|row| {
+            let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+            fields.join(",") + "\n"
+        })
+        .collect();
+    let tmp_path = format!("{}.tmp", cache_path);
+    if fs::write(&tmp_path, text)
+        .and_then(|()| fs::rename(&tmp_path, cache_path))
+        .is_err()
+    {
+        return Err(PyValueError::new_err("Cannot write cache."));
+    }
+
+    Ok(hash)
+}
+
+#[cfg(not(feature = "cache"))]
+pub(crate) fn hash_cached(
+    _fpath: &str,
+    _hash_size: u32,
+    _algo: Algorithm,
+    _cache_path: &str,
+) -> PyResult<ImageHash> {
+    Err(PyValueError::new_err(
+        "Hash caching is not enabled, build with the `cache` feature.",
+    ))
+}
+
+// Canonical path, size in bytes and mtime in nanoseconds of a file.
+#[cfg(feature = "cache")]
+fn file_key(fpath: &str) -> Option<(String, u64, u128)> {
+    let path = fs::canonicalize(fpath).ok()?;
+    let meta = fs::metadata(&path).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((path.to_str()?.to_string(), meta.len(), mtime.as_nanos()))
+}
@@ -0,0 +1,282 @@
+// Low frequency DCT-II coefficients used by the perceptual hash.
+//
+// All backends compute the same unnormalized sum
+// `X[v][u] = sum_y sum_x p(x, y) * cos(pi / N * (x + 0.5) * u) * cos(pi / N * (y + 0.5) * v)`
+// for `u, v < size`, they only differ in cost. The FFT backend requires the `fft` feature.
+// They add terms in different orders, so the coefficients only agree up to floating-point
+// rounding; the phash threshold ignores differences of that size.
+//
+// The separable backends can spread their row and column passes over the rayon thread
+// pool. Every row is still summed in the same order, so the result is bit-identical to
+// the serial computation.
+
+use std::f64::consts::PI;
+
+use image::GrayImage;
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rayon::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DctBackend {
+    // Direct two-dimensional sum, O(size^2 * N^2).
+    Naive,
+    // Rows then columns, O(size * N^2).
+    Separable,
+    // Rows then columns through a real FFT, O(N^2 log N).
+    Fft,
+}
+
+impl DctBackend {
+    pub(crate) fn parse(name: Option<String>) -> PyResult<DctBackend> {
+        let name = match name {
+            Some(name) => name,
+            None => return Ok(DctBackend::Separable),
+        };
+
+        match name.to_lowercase().as_str() {
+            "naive" => Ok(DctBackend::Naive),
+            "separable" => Ok(DctBackend::Separable),
+            "fft" if cfg!(feature = "fft") => Ok(DctBackend::Fft),
+            "fft" => Err(PyValueError::new_err(
+                "FFT backend is not enabled, build with the `fft` feature.",
+            )),
+            _ => Err(PyValueError::new_err(format!("Unknown backend: {}", name))),
+        }
+    }
+}
+
+// Cosine tables of the naive and separable backends for one image size, so they can be
+// computed once and shared by every hash of that size.
+pub(crate) struct CosineBasis {
+    size: u32,
+    cos_x: Vec<Vec<f64>>,
+    cos_y: Vec<Vec<f64>>,
+}
+
+impl CosineBasis {
+    pub(crate) fn new(size: u32, width: u32, height: u32) -> CosineBasis {
+        CosineBasis {
+            size,
+            cos_x: cos_table(size, width),
+            cos_y: cos_table(size, height),
+        }
+    }
+
+    fn fits(&self, size: u32, width: u32, height: u32) -> bool {
+        self.size == size
+            && self.cos_x.first().map_or(0, Vec::len) == width as usize
+            && self.cos_y.first().map_or(0, Vec::len) == height as usize
+    }
+}
+
+// Computes the top-left `size` x `size` block of the image's DCT, indexed `[v][u]`.
+// A `basis` built for another size is ignored.
+pub(crate) fn dct_low_freq(
+    img: &GrayImage,
+    size: u32,
+    backend: DctBackend,
+    parallel: bool,
+    basis: Option<&CosineBasis>,
+) -> Vec<Vec<f64>> {
+    // The FFT backend has no tables to reuse.
+    if backend == DctBackend::Fft {
+        return fft(img, size, parallel);
+    }
+
+    let (width, height) = img.dimensions();
+    let owned;
+    let basis = match basis {
+        Some(basis) if basis.fits(size, width, height) => basis,
+        _ => {
+            owned = CosineBasis::new(size, width, height);
+            &owned
+        }
+    };
+    if backend == DctBackend::Naive {
+        naive(img, size, basis)
+    } else {
+        separable(img, size, parallel, basis)
+    }
+}
+
+// Collects `f(i)` for `i` in `0..len`, on the rayon pool when `parallel` is set.
+fn map_indices<F>(len: usize, parallel: bool, f: F) -> Vec<Vec<f64>>
+where
+    F: Fn(usize) -> Vec<f64> + Sync + Send,
+{
+    if parallel {
+        (0..len).into_par_iter().map(f).collect()
+    } else {
+        (0..len).map(f).collect()
+    }
+}
+
+// `table[k][n]` holds the basis value of frequency `k` at position `n`.
+fn cos_table(size: u32, len: u32) -> Vec<Vec<f64>> {
+    let n = len as f64;
+    (0..size)
+        .map(|k| {
+            (0..len)
+                .map(|x| (PI / n * (x as f64 + 0.5) * k as f64).cos())
+                .collect()
+        })
+        .collect()
+}
+
+fn naive(img: &GrayImage, size: u32, basis: &CosineBasis) -> Vec<Vec<f64>> {
+    let (width, height) = img.dimensions();
+    let (cos_x, cos_y) = (&basis.cos_x, &basis.cos_y);
+
+    let mut coefs = vec![vec![0.0f64; size as usize]; size as usize];
+    for (v, row) in coefs.iter_mut().enumerate() {
+        for (u, coef) in row.iter_mut().enumerate() {
+            let mut sum = 0.0f64;
+            for y in 0..height {
+                for x in 0..width {
+                    let value = img.get_pixel(x, y).0[0] as f64;
+                    sum += value * cos_x[u][x as usize] * cos_y[v][y as usize];
+                }
+            }
+            *coef = sum;
+        }
+    }
+    coefs
+}
+
+fn separable(img: &GrayImage, size: u32, parallel: bool, basis: &CosineBasis) -> Vec<Vec<f64>> {
+    let (width, height) = img.dimensions();
+    let (cos_x, cos_y) = (&basis.cos_x, &basis.cos_y);
+
+    // rows[y][u]: DCT of every row along x.
+    let rows = map_indices(height as usize, parallel, |y| {
+        cos_x
+            .iter()
+            .map(|basis| {
+                (0..width)
+                    .map(|x| img.get_pixel(x, y as u32).0[0] as f64 * basis[x as usize])
+                    .sum()
+            })
+            .collect()
+    });
+
+    // Then every column of `rows` along y.
+    map_indices(size as usize, parallel, |v| {
+        (0..size as usize)
+            .map(|u| rows.iter().zip(&cos_y[v]).map(|(row, c)| row[u] * c).sum())
+            .collect()
+    })
+}
+
+#[cfg(feature = "fft")]
+fn fft(img: &GrayImage, size: u32, parallel: bool) -> Vec<Vec<f64>> {
+    use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+    let (width, height) = img.dimensions();
+    let mut planner = FftPlanner::<f64>::new();
+    let row_plan = planner.plan_fft_forward(2 * width as usize);
+    let col_plan = planner.plan_fft_forward(2 * height as usize);
+
+    // DCT-II of `signal` through an FFT of its mirrored extension `[x, reversed(x)]`:
+    // `X[k] = Re(Y[k] * exp(-i * pi * k / 2N)) / 2`.
+    let dct = |plan: &dyn Fft<f64>, signal: Vec<f64>| -> Vec<f64> {
+        let len = signal.len();
+        let mut buf: Vec<Complex<f64>> = signal
+            .iter()
+            .chain(signal.iter().rev())
+            .map(|v| Complex::new(*v, 0.0))
+            .collect();
+        plan.process(&mut buf);
+
+        (0..size as usize)
+            .map(|k| {
+                let angle = -PI * k as f64 / (2 * len) as f64;
+                (buf[k] * Complex::new(angle.cos(), angle.sin())).re / 2.0
+            })
+            .collect()
+    };
+
+    let rows = map_indices(height as usize, parallel, |y| {
+        let row = (0..width)
+            .map(|x| img.get_pixel(x, y as u32).0[0] as f64)
+            .collect();
+        dct(row_plan.as_ref(), row)
+    });
+
+    let cols = map_indices(size as usize, parallel, |u| {
+        let col = rows.iter().map(|row| row[u]).collect();
+        dct(col_plan.as_ref(), col)
+    });
+
+    // `cols` is indexed [u][v], transpose it back to [v][u].
+    (0..size as usize)
+        .map(|v| (0..size as usize).map(|u| cols[u][v]).collect())
+        .collect()
+}
+
+#[cfg(not(feature = "fft"))]
+fn fft(img: &GrayImage, size: u32, parallel: bool) -> Vec<Vec<f64>> {
+    // Unreachable, `DctBackend::parse` refuses the FFT backend without the feature.
+    let (width, height) = img.dimensions();
+    separable(img, size, parallel, &CosineBasis::new(size, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Smooth gradient with a deterministic speckle on top, so every frequency is present.
+    fn test_image(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| {
+            let speckle = (x * 7919 + y * 104729) % 61;
+            image::Luma([((x * 3 + y * 5 + speckle) % 256) as u8])
+        })
+    }
+
+    fn assert_close(a: &[Vec<f64>], b: &[Vec<f64>]) {
+        for (row_a, row_b) in a.iter().zip(b) {
+            for (x, y) in row_a.iter().zip(row_b) {
+                assert!((x - y).abs() <= 1e-9 * x.abs().max(1.0), "{} != {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn backends_agree() {
+        let img = test_image(32, 24);
+        let naive = dct_low_freq(&img, 8, DctBackend::Naive, false, None);
+        let separable = dct_low_freq(&img, 8, DctBackend::Separable, false, None);
+        assert_eq!(naive.len(), 8);
+        assert_close(&naive, &separable);
+        #[cfg(feature = "fft")]
+        assert_close(&naive, &dct_low_freq(&img, 8, DctBackend::Fft, false, None));
+    }
+
+    #[test]
+    fn parallel_is_bit_identical() {
+        let img = test_image(64, 64);
+        for backend in [DctBackend::Naive, DctBackend::Separable] {
+            assert_eq!(
+                dct_low_freq(&img, 8, backend, false, None),
+                dct_low_freq(&img, 8, backend, true, None)
+            );
+        }
+    }
+
+    #[test]
+    fn mismatched_basis_is_ignored() {
+        let img = test_image(32, 32);
+        let wrong = CosineBasis::new(8, 16, 16);
+        assert_eq!(
+            dct_low_freq(&img, 8, DctBackend::Separable, false, Some(&wrong)),
+            dct_low_freq(&img, 8, DctBackend::Separable, false, None)
+        );
+    }
+
+    #[test]
+    fn constant_image_has_only_dc() {
+        let img = GrayImage::from_pixel(16, 16, image::Luma([10]));
+        let coefs = dct_low_freq(&img, 4, DctBackend::Separable, false, None);
+        assert!((coefs[0][0] - 10.0 * 256.0).abs() < 1e-9);
+        assert!(coefs.iter().flatten().skip(1).all(|c| c.abs() < 1e-9));
+    }
+}
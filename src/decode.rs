@@ -0,0 +1,307 @@
+// Image decoding shared by all hashers.
+//
+// Most formats go straight through `image::open`. The `image` crate rejects CMYK TIFFs,
+// so those are decoded with the `tiff` crate and converted to RGB here. Palette-indexed
+// PNGs are already expanded to RGB(A) by the `image` PNG decoder.
+//
+// JPEGs cut short by an interrupted download fail to decode. Unless decoding is strict,
+// they are retried with the missing end of image marker appended, which yields the rows
+// that were received with the rest left gray. Such a hash is only a best effort.
+//
+// Later pages of multi-page TIFFs are decoded with the `tiff` crate as well, since
+// `image::open` only ever returns the first one.
+//
+// Embedded color profiles (ICC) are never applied: a hash only depends on the stored
+// pixel values, so the same pixels hash the same whatever profile is attached, even
+// though viewers honoring the profile may show them differently.
+//
+// Animated GIFs, PNGs and WebPs can be decoded frame by frame, each frame composited over
+// the previous ones as a viewer shows it. Animated WebPs are recognized by the VP8X
+// header of the `image` WebP decoder, which the crate's default features include.
+//
+// Images can also come from base64 strings, optionally wrapped in a data URL, as sent by
+// browsers.
+
+use std::fs::{self, File};
+use std::io::BufReader;
+
+use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+use image::error::UnsupportedError;
+use image::{AnimationDecoder, DynamicImage, ImageBuffer, ImageError, ImageFormat, RgbImage};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::ColorType;
+
+// Opens the first page of an image from disk, recovering truncated JPEGs.
+pub(crate) fn open_image(fpath: &str) -> PyResult<DynamicImage> {
+    open_image_with(fpath, false, 0)
+}
+
+// Opens page `page` of an image from disk, counting from 0. Only TIFFs have more than
+// one page. When `strict`, truncated JPEGs are not recovered and the decoder's error is
+// raised instead.
+pub(crate) fn open_image_with(fpath: &str, strict: bool, page: u32) -> PyResult<DynamicImage> {
+    if page > 0 {
+        return open_tiff_page(fpath, page);
+    }
+
+    let err = match image::open(fpath) {
+        Ok(im) => return Ok(im),
+        Err(e) => e,
+    };
+    if let Some(im) = open_cmyk_tiff(fpath) {
+        return Ok(im);
+    }
+
+    if strict {
+        return Err(PyValueError::new_err(format!(
+            "Cannot open image: {}.",
+            err
+        )));
+    }
+    match open_truncated_jpeg(fpath) {
+        Some(im) => Ok(im),
+        None => Err(PyValueError::new_err("Cannot open image.")),
+    }
+}
+
+// Decodes a JPEG missing its end of image marker, or returns `None` for anything else.
+fn open_truncated_jpeg(fpath: &str) -> Option<DynamicImage> {
+    let mut data = fs::read(fpath).ok()?;
+    if !data.starts_with(&[0xFF, 0xD8]) || data.ends_with(&[0xFF, 0xD9]) {
+        return None;
+    }
+
+    data.extend_from_slice(&[0xFF, 0xD9]);
+    image::load_from_memory_with_format(&data, ImageFormat::Jpeg).ok()
+}
+
+// Frames of an animation, decoded lazily.
+pub(crate) type FrameIter = Box<dyn Iterator<Item = PyResult<DynamicImage>>>;
+
+// Opens the frames of an animated GIF, PNG or WebP. Any other image has a single frame.
+pub(crate) fn open_frames(fpath: &str) -> PyResult<FrameIter> {
+    let format = image::io::Reader::open(fpath)
+        .and_then(|reader| reader.with_guessed_format())
+        .ok()
+        .and_then(|reader| reader.format());
+    let reader = match File::open(fpath) {
+        Ok(file) => BufReader::new(file),
+        Err(_e) => return Err(PyValueError::new_err("Cannot open image.")),
+    };
+
+    let frames = match format {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader).ok().map(|d| d.into_frames()),
+        Some(ImageFormat::Png) => PngDecoder::new(reader)
+            .ok()
+            .filter(|d| d.is_apng())
+            .map(|d| d.apng().into_frames()),
+        Some(ImageFormat::WebP) => match WebPDecoder::new(reader) {
+            Ok(decoder) => Some(decoder.into_frames()),
+            Err(ImageError::Unsupported(e)) => return Err(unsupported_webp(e)),
+            Err(_e) => None,
+        },
+        _ => None,
+    };
+
+    // Still WebPs decode to no frames at all.
+    let mut frames = match frames {
+        Some(frames) => frames.peekable(),
+        None => return Ok(Box::new(std::iter::once(open_image(fpath)))),
+    };
+    if frames.peek().is_none() {
+        return Ok(Box::new(std::iter::once(open_image(fpath))));
+    }
+
+    let is_webp = format == Some(ImageFormat::WebP);
+    Ok(Box::new(frames.map(move |frame| match frame {
+        Ok(frame) => Ok(DynamicImage::ImageRgba8(frame.into_buffer())),
+        Err(ImageError::Unsupported(e)) if is_webp => Err(unsupported_webp(e)),
+        Err(_e) => Err(PyValueError::new_err("Cannot decode animation frame.")),
+    })))
+}
+
+// Error for an animated WebP using a feature the `image` WebP decoder does not support,
+// naming that feature rather than failing as an undecodable file.
+fn unsupported_webp(e: UnsupportedError) -> PyErr {
+    PyValueError::new_err(format!(
+        "Animated WebP is not fully supported by the image decoder: {}",
+        e
+    ))
+}
+
+// Decodes an 8-bit CMYK TIFF into RGB, or returns `None` for anything else.
+fn open_cmyk_tiff(fpath: &str) -> Option<DynamicImage> {
+    let mut decoder = Decoder::new(BufReader::new(File::open(fpath).ok()?)).ok()?;
+    if decoder.colortype().ok()? != ColorType::CMYK(8) {
+        return None;
+    }
+    read_tiff_image(&mut decoder)
+}
+
+// Decodes page `page` of a multi-page TIFF. Any other file only has page 0.
+fn open_tiff_page(fpath: &str, page: u32) -> PyResult<DynamicImage> {
+    let out_of_range = || PyValueError::new_err(format!("Page {} is out of range.", page));
+    let file = match File::open(fpath) {
+        Ok(file) => file,
+        Err(_e) => return Err(PyValueError::new_err("Cannot open image.")),
+    };
+    let mut decoder = match Decoder::new(BufReader::new(file)) {
+        Ok(decoder) => decoder,
+        Err(_e) => return Err(out_of_range()),
+    };
+
+    for _ in 0..page {
+        if !decoder.more_images() || decoder.next_image().is_err() {
+            return Err(out_of_range());
+        }
+    }
+
+    match read_tiff_image(&mut decoder) {
+        Some(im) => Ok(im),
+        None => Err(PyValueError::new_err("Cannot open image.")),
+    }
+}
+
+// Decodes the current page of a TIFF, or returns `None` for unsupported color types.
+fn read_tiff_image(decoder: &mut Decoder<BufReader<File>>) -> Option<DynamicImage> {
+    let (width, height) = decoder.dimensions().ok()?;
+    match (decoder.colortype().ok()?, decoder.read_image().ok()?) {
+        (ColorType::Gray(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::CMYK(8), DecodingResult::U8(data)) => {
+            RgbImage::from_raw(width, height, cmyk_to_rgb(&data)).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+        }
+        _ => None,
+    }
+}
+
+// Converts CMYK samples to RGB, the same way the `image` crate's JPEG decoder does.
+fn cmyk_to_rgb(cmyk: &[u8]) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(cmyk.len() / 4 * 3);
+    for px in cmyk.chunks_exact(4) {
+        let k = 255 - px[3] as u32;
+        for ch in &px[..3] {
+            rgb.push(((255 - *ch as u32) * k / 255) as u8);
+        }
+    }
+    rgb
+}
+
+// Decodes an image from base64, with or without a `data:image/...;base64,` prefix.
+pub(crate) fn open_base64_image(data: &str) -> PyResult<DynamicImage> {
+    let payload = match data.strip_prefix("data:") {
+        Some(url) => match url.split_once(";base64,") {
+            Some((_mime, payload)) => payload,
+            None => return Err(PyValueError::new_err("Data URL is not base64 encoded.")),
+        },
+        None => data,
+    };
+
+    let bytes = match decode_base64(payload) {
+        Some(bytes) => bytes,
+        None => return Err(PyValueError::new_err("Invalid base64.")),
+    };
+    match image::load_from_memory(&bytes) {
+        Ok(im) => Ok(im),
+        Err(_e) => Err(PyValueError::new_err("Cannot open image.")),
+    }
+}
+
+// Decodes standard or URL-safe base64, ignoring whitespace and with optional padding.
+// Returns `None` on any other character outside the alphabets, or on a truncated final
+// group.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches(|c: char| c == '=' || c.is_ascii_whitespace());
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        buffer = buffer << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    // A single leftover character cannot encode a whole byte.
+    if bits >= 6 {
+        return None;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_base64_rfc4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("Zg==", "f"),
+            ("Zm8=", "fo"),
+            ("Zm9v", "foo"),
+            ("Zm9vYg==", "foob"),
+            ("Zm9vYmE=", "fooba"),
+            ("Zm9vYmFy", "foobar"),
+        ];
+        for (encoded, decoded) in vectors {
+            assert_eq!(decode_base64(encoded).unwrap(), decoded.as_bytes());
+        }
+    }
+
+    #[test]
+    fn decode_base64_padding_is_optional() {
+        assert_eq!(decode_base64("Zg").unwrap(), b"f");
+        assert_eq!(decode_base64("Zm8").unwrap(), b"fo");
+    }
+
+    #[test]
+    fn decode_base64_ignores_whitespace() {
+        assert_eq!(decode_base64(" Zm9v\r\nYmFy\n").unwrap(), b"foobar");
+        assert_eq!(decode_base64("Zm9v\tYg==\n").unwrap(), b"foob");
+    }
+
+    #[test]
+    fn decode_base64_url_safe() {
+        assert_eq!(decode_base64("-_8").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode_base64("+/8=").unwrap(), [0xfb, 0xff]);
+    }
+
+    #[test]
+    fn decode_base64_rejects_invalid_input() {
+        assert_eq!(decode_base64("Zm9v!"), None);
+        assert_eq!(decode_base64("Zg==Zg=="), None);
+        assert_eq!(decode_base64("Zm9vY"), None);
+    }
+}
@@ -0,0 +1,143 @@
+// Object-oriented entry point: a `Hasher` built once from a `HashConfig` and reused for
+// every file.
+//
+// Everything that only depends on the configuration is resolved when the hasher is built:
+// options are validated, the resize filter is picked, and for phash the cosine tables of
+// the DCT are computed. Hashing a file then only decodes, resizes and transforms it, and
+// gives the same bits as the free function with the same options.
+
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+
+use crate::{
+    check_clahe, check_hash_size, check_snap_levels, coefficient_positions, decode::open_image,
+    matte_option, parse_channel, parse_filter, Algorithm, CosineBasis, DctBackend, HashOptions,
+    ImageHash, PadMode,
+};
+
+// Validated options of a `Hasher`, named and defaulted like the arguments of the free
+// functions. Options that do not apply to `algo` are ignored.
+#[pyclass]
+#[derive(Clone)]
+pub(crate) struct HashConfig {
+    algo: Algorithm,
+    hash_size: u32,
+    opts: HashOptions,
+}
+
+#[pymethods]
+impl HashConfig {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        algo: String,
+        hash_size: u32,
+        highfreq_factor: Option<u32>,
+        background: Option<(u8, u8, u8)>,
+        filter: Option<String>,
+        equalize: Option<bool>,
+        crop_to_square: Option<bool>,
+        pad_mode: Option<String>,
+        linearize_srgb: Option<bool>,
+        channel: Option<String>,
+        clahe: Option<(u32, f64)>,
+        drop_dc: Option<bool>,
+        backend: Option<String>,
+        center: Option<bool>,
+        keep: Option<u32>,
+        center_weight: Option<f64>,
+        parallel: Option<bool>,
+        blur: Option<f32>,
+        snap_to_grid: Option<u32>,
+        unpremultiply: Option<bool>,
+        uncertain_epsilon: Option<f64>,
+        auto_deskew: Option<bool>,
+        remove_matte: Option<bool>,
+        matte_tolerance: Option<u8>,
+        saliency_crop: Option<bool>,
+    ) -> PyResult<HashConfig> {
+        check_hash_size(hash_size)?;
+        let algo = Algorithm::parse(&algo)?;
+        if matches!(algo, Algorithm::Perceptual) {
+            coefficient_positions(hash_size, keep)?;
+        }
+        let opts = HashOptions {
+            background,
+            highfreq_factor: highfreq_factor.unwrap_or(HashOptions::default().highfreq_factor),
+            filter: Some(parse_filter(filter)?.unwrap_or(algo.default_filter())),
+            equalize: equalize.unwrap_or(false),
+            crop_to_square: crop_to_square.unwrap_or(false),
+            pad_mode: PadMode::parse(pad_mode)?,
+            linearize_srgb: linearize_srgb.unwrap_or(false),
+            channel: parse_channel(channel)?,
+            clahe: check_clahe(clahe)?,
+            drop_dc: drop_dc.unwrap_or(true),
+            dct_backend: DctBackend::parse(backend)?,
+            center: center.unwrap_or(false),
+            keep,
+            center_weight,
+            parallel_dct: parallel.unwrap_or(false),
+            blur,
+            snap_levels: check_snap_levels(snap_to_grid)?,
+            unpremultiply: unpremultiply.unwrap_or(false),
+            uncertain_epsilon,
+            deskew: auto_deskew.unwrap_or(false),
+            matte_tolerance: matte_option(remove_matte, matte_tolerance),
+            saliency_crop: saliency_crop.unwrap_or(false),
+            ..Default::default()
+        };
+        Ok(HashConfig {
+            algo,
+            hash_size,
+            opts,
+        })
+    }
+
+    #[getter]
+    fn get_algo(&self) -> PyResult<&'static str> {
+        Ok(self.algo.name())
+    }
+
+    #[getter]
+    fn get_hash_size(&self) -> PyResult<u32> {
+        Ok(self.hash_size)
+    }
+}
+
+// Hashes files with a fixed configuration.
+#[pyclass]
+pub(crate) struct Hasher {
+    config: HashConfig,
+}
+
+#[pymethods]
+impl Hasher {
+    #[new]
+    fn new(config: HashConfig) -> Hasher {
+        let mut config = config;
+        if matches!(config.algo, Algorithm::Perceptual)
+            && config.opts.dct_backend != DctBackend::Fft
+        {
+            // phash always transforms a square image of this side.
+            let side = config.hash_size * config.opts.highfreq_factor;
+            config.opts.dct_basis = Some(Arc::new(CosineBasis::new(config.hash_size, side, side)));
+        }
+        Hasher { config }
+    }
+
+    #[getter]
+    fn get_config(&self) -> PyResult<HashConfig> {
+        Ok(self.config.clone())
+    }
+
+    // Hashes a file. The GIL is released while hashing.
+    fn hash(&self, py: Python<'_>, fpath: String) -> PyResult<ImageHash> {
+        let config = &self.config;
+        py.allow_threads(|| {
+            config
+                .algo
+                .hash_image(open_image(&fpath)?, config.hash_size, &config.opts)
+        })
+    }
+}
@@ -1,199 +1,3821 @@
-use std::f64::consts::PI;
-
-use pyo3::{exceptions::PyValueError, prelude::*};
-
-#[pyclass]
-struct ImageHash {
-    bool_values: Vec<bool>,
-    values: Vec<u8>,
-    hash_size: usize,
-}
-
-#[pymethods]
-impl ImageHash {
-    #[getter]
-    fn get_bool_values(&self) -> PyResult<Vec<bool>> {
-        Ok(self.bool_values.clone())
-    }
-
-    #[getter]
-    fn get_values(&self) -> PyResult<Vec<u8>> {
-        Ok(self.values.clone())
-    }
-
-    #[getter]
-    fn get_hash_size(&self) -> PyResult<usize> {
-        Ok(self.hash_size)
-    }
-
-    pub fn distance(&self, other: &ImageHash) -> PyResult<u32> {
-        if self.hash_size != other.hash_size {
-            return Err(PyValueError::new_err("Unmatch size"));
-        }
-
-        let mut count = 0;
-        let mut i: usize = 0;
-        while i < self.hash_size.pow(2) {
-            if self.bool_values.get(i).unwrap() != other.bool_values.get(i).unwrap() {
-                count += 1
-            };
-            i += 1;
-        }
-
-        Ok(count)
-    }
-}
-
-// Hashes an image using average hash
-#[pyfunction]
-fn ahash(fpath: String, hash_size: u32) -> PyResult<ImageHash> {
-    let img = match image::open(fpath) {
-        Ok(im) => im,
-        Err(_e) => return Err(PyValueError::new_err("Cannot open image.")),
-    };
-    let resized = img
-        .resize_exact(hash_size, hash_size, image::imageops::FilterType::Lanczos3)
-        .to_luma8();
-
-    let hashpow = hash_size.pow(2);
-    let avg: f64 =
-        resized.pixels().map(|px| px.0[0] as f64).sum::<f64>() / resized.pixels().len() as f64;
-
-    let mut bool_result = vec![false; hashpow as usize];
-    let mut result: Vec<u8> = vec![0; (hashpow / 8) as usize];
-
-    for (c, px) in resized.pixels().enumerate() {
-        let cmp = px.0[0] as f64 > avg;
-        bool_result[c] = cmp;
-        if cmp {
-            result[c / 8] |= 1 << (c % 8);
-        } else {
-            result[c / 8] |= 0 << (c % 8);
-        }
-    }
-
-    Ok(ImageHash {
-        bool_values: bool_result,
-        values: result,
-        hash_size: hash_size as usize,
-    })
-}
-
-// Hashes an image using perceptual hash
-#[pyfunction]
-fn phash(fpath: String, hash_size: u32, highfreq_factor: u32) -> PyResult<ImageHash> {
-    let img = match image::open(fpath) {
-        Ok(im) => im,
-        Err(_e) => return Err(PyValueError::new_err("Cannot open image.")),
-    };
-
-    let img_size = hash_size * highfreq_factor;
-    let resized = img
-        .resize_exact(img_size, img_size, image::imageops::FilterType::Lanczos3)
-        .to_luma8();
-
-    let mut dct_arr =
-        vec![vec![0.0f64; (hash_size + 1).try_into().unwrap()]; hash_size.try_into().unwrap()];
-
-    for i in 0..hash_size {
-        // Exclude first term of every y axis
-        for j in 1..hash_size + 1 {
-            #[allow(non_snake_case)]
-            let N = img_size.pow(2) as f64;
-            let k = (i * img_size + j) as f64;
-            let mut sum = 0.0f64;
-
-            for y in 0..img_size {
-                for x in 0..img_size {
-                    let value = resized.get_pixel(x, y).0[0] as f64;
-                    let n = y * img_size + x;
-                    sum += value * (PI / N * (n as f64 + 0.5) * k).cos();
-                }
-            }
-
-            dct_arr[i as usize][(j - 1) as usize] = sum;
-        }
-    }
-
-    let hashpow = hash_size.pow(2);
-    let avg = dct_arr.iter().flat_map(|row| row.iter()).sum::<f64>() / hashpow as f64;
-
-    let mut bool_result = vec![false; hashpow as usize];
-    let mut result: Vec<u8> = vec![0; (hashpow / 8) as usize];
-
-    for i in 0..hash_size {
-        for j in 0..hash_size {
-            let c = (i * hash_size + j) as usize;
-            let cmp = dct_arr[i as usize][j as usize] > avg;
-            bool_result[c] = cmp;
-            if cmp {
-                result[c / 8] |= 1 << (c % 8);
-            } else {
-                result[c / 8] |= 0 << (c % 8);
-            }
-        }
-    }
-
-    Ok(ImageHash {
-        bool_values: bool_result,
-        values: result,
-        hash_size: hash_size as usize,
-    })
-}
-
-// Hashes an image using difference hash
-#[pyfunction]
-fn dhash(fpath: String, hash_size: u32) -> PyResult<ImageHash> {
-    let img = match image::open(fpath) {
-        Ok(im) => im,
-        Err(_e) => return Err(PyValueError::new_err("Cannot open image.")),
-    };
-    let resized = img
-        .resize_exact(
-            hash_size + 1,
-            hash_size + 1,
-            image::imageops::FilterType::Lanczos3,
-        )
-        .to_luma8();
-
-    let hashpow = hash_size.pow(2);
-    let mut bool_result = vec![false; hashpow as usize];
-    let mut result: Vec<u8> = vec![0; (hashpow / 8) as usize];
-
-    let mut y = 0;
-    while y < hash_size {
-        let mut x = 0;
-        while x < hash_size {
-            let c = (y * 8 + x) as usize;
-            let left_pixel = resized.get_pixel(x, y).0[0];
-            let right_pixel = resized.get_pixel(x + 1, y).0[0];
-
-            let cmp = left_pixel > right_pixel;
-            bool_result[c] = cmp;
-            if cmp {
-                result[c / 8] |= 1 << (c % 8);
-            } else {
-                result[c / 8] |= 0 << (c % 8);
-            }
-
-            x += 1;
-        }
-        y += 1;
-    }
-
-    Ok(ImageHash {
-        bool_values: bool_result,
-        values: result,
-        hash_size: hash_size as usize,
-    })
-}
-
-/// A Python module implemented in Rust.
-#[pymodule]
-fn dif(_py: Python, m: &PyModule) -> PyResult<()> {
-    m.add_class::<ImageHash>()?;
-    m.add_function(wrap_pyfunction!(ahash, m)?)?;
-    m.add_function(wrap_pyfunction!(dhash, m)?)?;
-    m.add_function(wrap_pyfunction!(phash, m)?)?;
-    Ok(())
-}
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::{
+    imageops, imageops::FilterType, ColorType, DynamicImage, GenericImageView, GrayImage,
+    ImageOutputFormat, Rgb, RgbImage,
+};
+use pyo3::{
+    buffer::PyBuffer,
+    exceptions::{PyImportError, PyValueError},
+    prelude::*,
+    types::{PyByteArray, PyBytes},
+};
+use rayon::prelude::*;
+
+mod align;
+mod archive;
+mod cache;
+mod dct;
+mod decode;
+mod hasher;
+mod saliency;
+mod skew;
+mod spectrum;
+mod thumb;
+mod tiled;
+mod video;
+
+use dct::{dct_low_freq, CosineBasis, DctBackend};
+use decode::{open_base64_image, open_frames, open_image, open_image_with};
+
+// Header of the binary container written by `save_hashes`.
+const HASHES_MAGIC: &[u8; 4] = b"DIFH";
+const HASHES_VERSION: u8 = 2;
+
+// Longest side `text_robust_hash` filters text at.
+const TEXT_FILTER_MAX_SIDE: u32 = 512;
+
+// Background used when flattening transparent images, unless the caller picks one.
+const DEFAULT_BACKGROUND: (u8, u8, u8) = (255, 255, 255);
+
+// Calibration of `match_probability` for hashes of unknown algorithm.
+const DEFAULT_CALIBRATION: (f64, f64) = (0.2, 35.0);
+
+// How far, in gray levels per channel, a pixel may stray from the matte color and still be
+// removed with it, unless the caller picks a tolerance.
+const DEFAULT_MATTE_TOLERANCE: u8 = 16;
+
+#[pyclass]
+struct ImageHash {
+    bool_values: Vec<bool>,
+    values: Vec<u8>,
+    // Dimensions of the bit grid, equal for the usual square hashes.
+    width: usize,
+    height: usize,
+    // Bits whose value was decided by a margin too small to trust.
+    uncertain: Vec<bool>,
+    // Algorithm that computed the hash, unknown for hashes built by hand or derived from
+    // other hashes.
+    algorithm: Option<Algorithm>,
+}
+
+#[pymethods]
+impl ImageHash {
+    // Builds a hash from its bits, deriving the packed `values` from them so the two
+    // representations are always consistent.
+    #[new]
+    pub fn new(bool_values: Vec<bool>, hash_size: usize) -> PyResult<ImageHash> {
+        ImageHash::new_rect(bool_values, hash_size, hash_size)
+    }
+
+    #[getter]
+    fn get_bool_values(&self) -> PyResult<Vec<bool>> {
+        Ok(self.bool_values.clone())
+    }
+
+    #[getter]
+    fn get_values(&self) -> PyResult<Vec<u8>> {
+        Ok(self.values.clone())
+    }
+
+    // Side of a square hash, or of the cell grid of a quad hash. Rectangular hashes have a
+    // `width` and `height` instead.
+    #[getter]
+    fn get_hash_size(&self) -> PyResult<usize> {
+        if matches!(self.algorithm, Some(Algorithm::Quad)) {
+            return Ok(self.height);
+        }
+        self.side()
+    }
+
+    #[getter]
+    fn get_width(&self) -> PyResult<usize> {
+        Ok(self.width)
+    }
+
+    #[getter]
+    fn get_height(&self) -> PyResult<usize> {
+        Ok(self.height)
+    }
+
+    fn __len__(&self) -> usize {
+        self.bool_values.len()
+    }
+
+    // Lowercase hex of the packed bytes, two digits per byte in `values` order.
+    pub fn to_hex(&self) -> String {
+        self.values.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    // The bits as a single integer, bit `c` of the hash (row by row from the top left)
+    // being bit `c` of the integer counting from the least significant one, so
+    // `bin(a ^ b).count("1")` is the distance. Only hashes of at most 128 bits fit.
+    //
+    // The order is the same on every platform, and the same as `values` read as a little
+    // endian number: the hex of `to_hex` spells the integer's bytes from the lowest one.
+    // A hash whose first bit alone is set is 1, one whose last bit of 64 alone is set is
+    // `1 << 63`.
+    pub fn to_int(&self) -> PyResult<u128> {
+        if self.values.len() > 16 {
+            return Err(PyValueError::new_err("Hash has more than 128 bits"));
+        }
+        let mut bytes = [0u8; 16];
+        bytes[..self.values.len()].copy_from_slice(&self.values);
+        Ok(u128::from_le_bytes(bytes))
+    }
+
+    // Square hash of side `hash_size` from an integer in the bit order of `to_int`, so
+    // `from_int(h.to_int(), side)` gives back `h`.
+    #[staticmethod]
+    pub fn from_int(value: u128, hash_size: usize) -> PyResult<ImageHash> {
+        let bits = hash_size * hash_size;
+        if bits > 128 {
+            return Err(PyValueError::new_err("Hash has more than 128 bits"));
+        }
+        if bits < 128 && value >> bits != 0 {
+            return Err(PyValueError::new_err(
+                "Value has bits set past the end of the hash",
+            ));
+        }
+        ImageHash::new(unpack_bits(&value.to_le_bytes(), bits), hash_size)
+    }
+
+    // One `path,algorithm,hash_size,hex` CSV row, without line ending, readable back by
+    // `load_csv`. The `hash_size` field holds the `widthxheight` of the bit grid, so
+    // rectangular hashes fit too. Missing path and algorithm are left empty.
+    pub fn to_csv_row(&self, path: Option<String>) -> PyResult<String> {
+        let fields = [
+            path.unwrap_or_default(),
+            self.algorithm
+                .map(Algorithm::name)
+                .unwrap_or("")
+                .to_string(),
+            format!("{}x{}", self.width, self.height),
+            self.to_hex(),
+        ];
+        Ok(fields
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>()
+            .join(","))
+    }
+
+    // Length of the packed `values`.
+    #[getter]
+    fn get_byte_len(&self) -> PyResult<usize> {
+        Ok(self.values.len())
+    }
+
+    #[getter]
+    fn get_algorithm(&self) -> PyResult<Option<&'static str>> {
+        Ok(self.algorithm.map(Algorithm::name))
+    }
+
+    #[getter]
+    fn get_uncertain(&self) -> PyResult<Vec<bool>> {
+        Ok(self.uncertain.clone())
+    }
+
+    pub fn distance(&self, other: &ImageHash) -> PyResult<u32> {
+        self.check_shape(other)?;
+
+        Ok(self.hamming(other))
+    }
+
+    // Distance to a hash given by its hex, as stored by `to_hex`, without building an
+    // `ImageHash` for it. The hex must encode exactly as many bits as this hash.
+    pub fn distance_hex(&self, other_hex: String) -> PyResult<u32> {
+        let values = match parse_hex(&other_hex) {
+            Some(values) if values.len() == self.values.len() => values,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Hex does not encode a hash of {} bytes",
+                    self.values.len()
+                )))
+            }
+        };
+        // Bits past the end of the hash are always zero in packed form.
+        let bits = self.bool_values.len();
+        if !bits.is_multiple_of(8) && values[bits / 8] >> (bits % 8) != 0 {
+            return Err(PyValueError::new_err(
+                "Hex has bits set past the end of the hash",
+            ));
+        }
+
+        Ok(self
+            .values
+            .iter()
+            .zip(&values)
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum())
+    }
+
+    // Distance to `other`, or None as soon as it is known to exceed `max`. Scanning a
+    // gallery with a tight threshold then skips most of the work for clear non-matches.
+    pub fn distance_bounded(&self, other: &ImageHash, max: u32) -> PyResult<Option<u32>> {
+        self.check_shape(other)?;
+
+        let mut distance = 0;
+        for (a, b) in self.values.iter().zip(&other.values) {
+            distance += (a ^ b).count_ones();
+            if distance > max {
+                return Ok(None);
+            }
+        }
+        Ok(Some(distance))
+    }
+
+    // Distance counting only the bits that are certain in both hashes.
+    pub fn certain_distance(&self, other: &ImageHash) -> PyResult<u32> {
+        self.check_shape(other)?;
+
+        let differing = (0..self.bool_values.len()).filter(|c| {
+            !self.uncertain[*c]
+                && !other.uncertain[*c]
+                && self.bool_values[*c] != other.bool_values[*c]
+        });
+        Ok(differing.count() as u32)
+    }
+
+    // Distance counting only the bits where `mask` is set, to ignore regions known to
+    // change between copies such as a watermark or a timestamp.
+    pub fn masked_distance(&self, other: &ImageHash, mask: Vec<bool>) -> PyResult<u32> {
+        self.check_shape(other)?;
+        if mask.len() != self.bool_values.len() {
+            return Err(PyValueError::new_err(
+                "Mask length does not match hash size",
+            ));
+        }
+
+        let packed_mask = pack_bits(&mask);
+        Ok(self
+            .values
+            .iter()
+            .zip(&other.values)
+            .zip(&packed_mask)
+            .map(|((a, b), m)| ((a ^ b) & m).count_ones())
+            .sum())
+    }
+
+    // Distance where each differing bit counts its weight instead of 1, for example with
+    // the weights from `learn_bit_weights` or derived from `bit_error_profile`.
+    pub fn weighted_distance(&self, other: &ImageHash, weights: Vec<f64>) -> PyResult<f64> {
+        self.check_shape(other)?;
+        if weights.len() != self.bool_values.len() {
+            return Err(PyValueError::new_err(
+                "Weights length does not match hash size",
+            ));
+        }
+
+        Ok(self
+            .bool_values
+            .iter()
+            .zip(&other.bool_values)
+            .zip(&weights)
+            .filter(|((a, b), _)| a != b)
+            .map(|(_, weight)| weight)
+            .sum())
+    }
+
+    // Smallest of the distance to `other` and to `other` with the bits of each byte
+    // reversed. A hash that was stored most significant bit first somewhere along the way
+    // is still at distance 0 from its original.
+    pub fn distance_either_order(&self, other: &ImageHash) -> PyResult<u32> {
+        let direct = self.distance(other)?;
+
+        // Only the low `len % 8` bits of the last byte are part of the hash.
+        let len = self.bool_values.len();
+        let reversed = self
+            .values
+            .iter()
+            .zip(&other.values)
+            .enumerate()
+            .map(|(i, (a, b))| {
+                let mut diff = a ^ b.reverse_bits();
+                if i == len / 8 {
+                    diff &= (1u8 << (len % 8)) - 1;
+                }
+                diff.count_ones()
+            })
+            .sum();
+
+        Ok(direct.min(reversed))
+    }
+
+    // Best effort normalized distance between square hashes of different sizes, such as an
+    // 8x8 and a 16x16 one. The larger hash is first pooled down to the smaller grid with
+    // `downsample`, so its side must be a multiple of the other's.
+    pub fn distance_rescaled(&self, other: &ImageHash) -> PyResult<f64> {
+        let (side, other_side) = (self.side()?, other.side()?);
+        let (small, large) = if side <= other_side {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let (small_side, large_side) = (side.min(other_side), side.max(other_side));
+        if small_side == 0 || !large_side.is_multiple_of(small_side) {
+            return Err(PyValueError::new_err(
+                "Hash sizes must be multiples of one another",
+            ));
+        }
+
+        let pooled = large.downsample((large_side / small_side) as u32)?;
+        Ok(small.hamming(&pooled) as f64 / small.bool_values.len() as f64)
+    }
+
+    // Smallest distance to any of the reference hashes, stopping early on an exact match.
+    pub fn min_distance_to(&self, references: Vec<PyRef<ImageHash>>) -> PyResult<u32> {
+        let mut min: Option<u32> = None;
+        for reference in &references {
+            self.check_shape(reference)?;
+
+            let distance = self.hamming(reference);
+            if distance == 0 {
+                return Ok(0);
+            }
+            min = Some(min.map_or(distance, |m| m.min(distance)));
+        }
+
+        match min {
+            Some(min) => Ok(min),
+            None => Err(PyValueError::new_err("No reference hashes given")),
+        }
+    }
+
+    // Histogram of the distances to every gallery hash, with `bins` equal width bins
+    // covering 0 to the hash's bit count inclusive. Useful to see how far apart matches
+    // and non-matches are before picking a threshold.
+    pub fn distance_histogram(
+        &self,
+        gallery: Vec<PyRef<ImageHash>>,
+        bins: u32,
+    ) -> PyResult<Vec<u32>> {
+        if bins == 0 {
+            return Err(PyValueError::new_err("Bins must be at least 1"));
+        }
+
+        let span = self.bool_values.len() as u64 + 1;
+        let mut histogram = vec![0u32; bins as usize];
+        for hash in &gallery {
+            let distance = self.distance(hash)? as u64;
+            histogram[(distance * bins as u64 / span) as usize] += 1;
+        }
+
+        Ok(histogram)
+    }
+
+    // Returns a new hash whose bit grid is rotated clockwise by `90 * times` degrees.
+    pub fn rotate90(&self, times: u32) -> PyResult<ImageHash> {
+        let n = self.side()?;
+
+        let mut grid = self.bool_values.clone();
+        for _ in 0..times % 4 {
+            let mut rotated = vec![false; n * n];
+            for y in 0..n {
+                for x in 0..n {
+                    rotated[y * n + x] = grid[(n - 1 - x) * n + y];
+                }
+            }
+            grid = rotated;
+        }
+
+        ImageHash::new(grid, n)
+    }
+
+    // Cheap pre-filter for the rotation and crop tolerant comparisons: whether the bit
+    // grid of `other`, under one of its 8 rotations and mirror images, is within
+    // `max_distance` bits of this one, a quarter of the bits by default. Only the grid is
+    // transformed, which matches a transformed image exactly for ahash and mhash and
+    // roughly for the other algorithms, so a `true` only means a closer look is worth it.
+    pub fn likely_transformed(
+        &self,
+        other: &ImageHash,
+        max_distance: Option<u32>,
+    ) -> PyResult<bool> {
+        self.check_shape(other)?;
+        let n = self.side()?;
+        let max_distance = max_distance.unwrap_or((n * n / 4) as u32) as usize;
+
+        // Source position in `other` of the bit at `(x, y)` under each transformation.
+        let source = |transform: u8, x: usize, y: usize| match transform {
+            0 => (x, y),
+            1 => (y, n - 1 - x),
+            2 => (n - 1 - x, n - 1 - y),
+            3 => (n - 1 - y, x),
+            4 => (n - 1 - x, y),
+            5 => (x, n - 1 - y),
+            6 => (y, x),
+            _ => (n - 1 - y, n - 1 - x),
+        };
+        Ok((0..8).any(|transform| {
+            let distance = (0..n * n)
+                .filter(|c| {
+                    let (sx, sy) = source(transform, c % n, c / n);
+                    self.bool_values[*c] != other.bool_values[sy * n + sx]
+                })
+                .count();
+            distance <= max_distance
+        }))
+    }
+
+    // Pools the bit grid into `factor` x `factor` cells, each set when most of its bits
+    // are (a tie leaves it unset). The coarser hash is cheap to compare as a first pass.
+    pub fn downsample(&self, factor: u32) -> PyResult<ImageHash> {
+        let n = self.side()?;
+        let factor = factor as usize;
+        if factor == 0 || !n.is_multiple_of(factor) {
+            return Err(PyValueError::new_err("Factor must divide the hash size"));
+        }
+
+        let coarse = n / factor;
+        let mut pooled = vec![false; coarse * coarse];
+        for (c, bit) in pooled.iter_mut().enumerate() {
+            let (cy, cx) = (c / coarse * factor, c % coarse * factor);
+            let set = (cy..cy + factor)
+                .flat_map(|y| (cx..cx + factor).map(move |x| y * n + x))
+                .filter(|i| self.bool_values[*i])
+                .count();
+            *bit = 2 * set > factor * factor;
+        }
+
+        ImageHash::new(pooled, coarse)
+    }
+
+    // Projects the bits (as +1/-1) onto `planes` random hyperplanes generated from `seed`
+    // and returns the signs as a bucket id. Similar hashes tend to land in the same bucket.
+    pub fn lsh_bucket(&self, planes: u32, seed: u64) -> PyResult<u64> {
+        if planes == 0 || planes > 64 {
+            return Err(PyValueError::new_err("Planes must be between 1 and 64"));
+        }
+
+        let mut rng = SplitMix64(seed);
+        let mut bucket = 0u64;
+        for plane in 0..planes {
+            let projection: f64 = self
+                .bool_values
+                .iter()
+                .map(|bit| {
+                    let weight = rng.next_signed_unit();
+                    if *bit {
+                        weight
+                    } else {
+                        -weight
+                    }
+                })
+                .sum();
+
+            if projection > 0.0 {
+                bucket |= 1 << plane;
+            }
+        }
+
+        Ok(bucket)
+    }
+
+    // Probability that `other` hashes the same image, from the normalized distance through
+    // the logistic curve `1 / (1 + exp(steepness * (distance - midpoint)))`. The curve is
+    // the one of the algorithm named by `algo_calibration`, by default the algorithm that
+    // computed this hash, and a generic one when that is unknown. Identical hashes score
+    // close to 1.0 and the score only falls as the distance grows.
+    pub fn match_probability(
+        &self,
+        other: &ImageHash,
+        algo_calibration: Option<String>,
+    ) -> PyResult<f64> {
+        self.check_shape(other)?;
+        let algo = match algo_calibration {
+            Some(name) => Some(Algorithm::parse(&name)?),
+            None => self.algorithm,
+        };
+        let (midpoint, steepness) = algo.map_or(DEFAULT_CALIBRATION, Algorithm::calibration);
+
+        let distance = self.hamming(other) as f64 / self.bool_values.len() as f64;
+        Ok(1.0 / (1.0 + (steepness * (distance - midpoint)).exp()))
+    }
+
+    // Shannon entropy in bits of the set/unset distribution. Close to 1.0 for a balanced
+    // hash, 0.0 for a degenerate one where every bit is the same.
+    pub fn entropy(&self) -> f64 {
+        if self.bool_values.is_empty() {
+            return 0.0;
+        }
+
+        let set = self.bool_values.iter().filter(|bit| **bit).count();
+        let p = set as f64 / self.bool_values.len() as f64;
+        [p, 1.0 - p]
+            .iter()
+            .filter(|q| **q > 0.0)
+            .fold(0.0, |total, q| total - q * q.log2())
+    }
+}
+
+impl ImageHash {
+    // Builds a hash with a `width` x `height` bit grid, stored row by row.
+    fn new_rect(bool_values: Vec<bool>, width: usize, height: usize) -> PyResult<ImageHash> {
+        if bool_values.len() != width * height {
+            return Err(PyValueError::new_err("Bit count does not match hash size"));
+        }
+
+        Ok(ImageHash {
+            values: pack_bits(&bool_values),
+            uncertain: vec![false; bool_values.len()],
+            bool_values,
+            width,
+            height,
+            algorithm: None,
+        })
+    }
+
+    // Side of the bit grid, which must be square.
+    fn side(&self) -> PyResult<usize> {
+        if self.width != self.height {
+            return Err(PyValueError::new_err("Hash is not square"));
+        }
+        Ok(self.width)
+    }
+
+    fn same_shape(&self, other: &ImageHash) -> bool {
+        (self.width, self.height) == (other.width, other.height)
+    }
+
+    fn check_shape(&self, other: &ImageHash) -> PyResult<()> {
+        if !self.same_shape(other) {
+            return Err(PyValueError::new_err("Unmatch size"));
+        }
+        Ok(())
+    }
+
+    // Marks the bits of `mask` as uncertain.
+    fn with_uncertain(mut self, mask: Vec<bool>) -> ImageHash {
+        self.uncertain = mask;
+        self
+    }
+
+    // Hash from the hex of `to_hex`, or `None` unless it holds exactly the bytes of a
+    // `width` x `height` grid.
+    fn from_hex(hex: &str, width: usize, height: usize) -> Option<ImageHash> {
+        let bits = width.checked_mul(height)?;
+        let values = parse_hex(hex).filter(|values| values.len() == bits.div_ceil(8))?;
+        ImageHash::new_rect(unpack_bits(&values, bits), width, height).ok()
+    }
+
+    // Records the algorithm that computed the hash.
+    fn with_algorithm(mut self, algorithm: Algorithm) -> ImageHash {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    // Hamming distance over the packed bytes. Sizes must already be known to match.
+    fn hamming(&self, other: &ImageHash) -> u32 {
+        // 8x8 hashes, by far the most common, fit a single word.
+        if let (Ok(a), Ok(b)) = (
+            <[u8; 8]>::try_from(self.values.as_slice()),
+            <[u8; 8]>::try_from(other.values.as_slice()),
+        ) {
+            return (u64::from_ne_bytes(a) ^ u64::from_ne_bytes(b)).count_ones();
+        }
+
+        self.values
+            .iter()
+            .zip(&other.values)
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+}
+
+// All the metrics of a comparison between two hashes. Hashes of different sizes cannot be
+// compared, in which case only `are_comparable` is meaningful and the others are None.
+#[pyclass]
+struct Comparison {
+    distance: Option<u32>,
+    normalized_distance: Option<f64>,
+    similarity: Option<f64>,
+    are_comparable: bool,
+}
+
+#[pymethods]
+impl Comparison {
+    #[getter]
+    fn get_distance(&self) -> PyResult<Option<u32>> {
+        Ok(self.distance)
+    }
+
+    #[getter]
+    fn get_normalized_distance(&self) -> PyResult<Option<f64>> {
+        Ok(self.normalized_distance)
+    }
+
+    #[getter]
+    fn get_similarity(&self) -> PyResult<Option<f64>> {
+        Ok(self.similarity)
+    }
+
+    #[getter]
+    fn get_are_comparable(&self) -> PyResult<bool> {
+        Ok(self.are_comparable)
+    }
+}
+
+// Where the time of a profiled hash went, in milliseconds: decoding the file, preparing
+// the grayscale thumbnail (resizing included), and computing the bits from it.
+#[pyclass]
+struct Timings {
+    decode_ms: f64,
+    resize_ms: f64,
+    transform_ms: f64,
+}
+
+#[pymethods]
+impl Timings {
+    #[getter]
+    fn get_decode_ms(&self) -> PyResult<f64> {
+        Ok(self.decode_ms)
+    }
+
+    #[getter]
+    fn get_resize_ms(&self) -> PyResult<f64> {
+        Ok(self.resize_ms)
+    }
+
+    #[getter]
+    fn get_transform_ms(&self) -> PyResult<f64> {
+        Ok(self.transform_ms)
+    }
+}
+
+// Times the phases of a hash for the `profile` option of the hashers.
+struct Profiler {
+    enabled: bool,
+    started: Instant,
+    decode: Duration,
+    prepare: Arc<Mutex<Duration>>,
+}
+
+impl Profiler {
+    // Starts the clock, making `opts` report the time spent preparing the thumbnail.
+    fn start(profile: Option<bool>, opts: &mut HashOptions) -> Profiler {
+        let enabled = profile.unwrap_or(false);
+        let prepare = Arc::new(Mutex::new(Duration::ZERO));
+        if enabled {
+            opts.prepare_time = Some(prepare.clone());
+        }
+        Profiler {
+            enabled,
+            started: Instant::now(),
+            decode: Duration::ZERO,
+            prepare,
+        }
+    }
+
+    // Marks the end of decoding.
+    fn decoded(&mut self) {
+        self.decode = self.started.elapsed();
+    }
+
+    // Returns `hash`, paired with its `Timings` when profiling.
+    fn finish(self, py: Python<'_>, hash: ImageHash) -> PyObject {
+        if !self.enabled {
+            return hash.into_py(py);
+        }
+
+        let total = self.started.elapsed();
+        let prepare = *self.prepare.lock().unwrap();
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let timings = Timings {
+            decode_ms: ms(self.decode),
+            resize_ms: ms(prepare),
+            transform_ms: ms(total.saturating_sub(self.decode + prepare)),
+        };
+        (hash, timings).into_py(py)
+    }
+}
+
+// Number of bytes the packed `values` of a `hash_size` x `hash_size` hash take, padding
+// included.
+#[pyfunction]
+fn packed_len(hash_size: u32) -> usize {
+    (hash_size as usize).pow(2).div_ceil(8)
+}
+
+// Unpacks the first `len` bits of a least significant bit first byte buffer.
+fn unpack_bits(packed: &[u8], len: usize) -> Vec<bool> {
+    (0..len)
+        .map(|c| packed[c / 8] >> (c % 8) & 1 == 1)
+        .collect()
+}
+
+// Small deterministic generator (SplitMix64) for reproducible random projections.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform value in [-1, 1).
+    fn next_signed_unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+}
+
+// Packs bits into bytes, least significant bit first. When the bit count is not a
+// multiple of 8, the unused high bits of the last byte are always zero, so the packed
+// form (and its hex) only depends on the hash bits.
+fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    let mut packed = vec![0u8; bits.len().div_ceil(8)];
+    for (c, bit) in bits.iter().enumerate() {
+        if *bit {
+            packed[c / 8] |= 1 << (c % 8);
+        }
+    }
+    packed
+}
+
+// Composites an image with alpha channel over a solid background color.
+// Images without alpha are returned untouched.
+fn flatten_alpha(img: DynamicImage, background: (u8, u8, u8)) -> DynamicImage {
+    if !img.color().has_alpha() {
+        return img;
+    }
+
+    let rgba = img.to_rgba8();
+    let bg = [background.0, background.1, background.2];
+    let mut flattened = RgbImage::new(rgba.width(), rgba.height());
+    for (src, dst) in rgba.pixels().zip(flattened.pixels_mut()) {
+        let alpha = src.0[3] as f64 / 255.0;
+        for (ch, out) in dst.0.iter_mut().enumerate() {
+            let value = src.0[ch] as f64 * alpha + bg[ch] as f64 * (1.0 - alpha);
+            *out = value.round() as u8;
+        }
+    }
+
+    DynamicImage::ImageRgb8(flattened)
+}
+
+// Divides the color channels of an image with alpha channel by its alpha, turning
+// premultiplied colors back into straight ones. Images without alpha are returned
+// untouched.
+fn unpremultiply_alpha(img: DynamicImage) -> DynamicImage {
+    if !img.color().has_alpha() {
+        return img;
+    }
+
+    let mut rgba = img.to_rgba8();
+    for px in rgba.pixels_mut() {
+        let alpha = px.0[3] as u32;
+        if alpha == 0 {
+            continue;
+        }
+        for ch in &mut px.0[..3] {
+            *ch = ((*ch as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+// Options accepted by the hashers. Each algorithm ignores the fields that do not
+// apply to it.
+#[derive(Clone)]
+struct HashOptions {
+    // Color transparent images are flattened over, white when unset.
+    background: Option<(u8, u8, u8)>,
+    equalize: bool,
+    // Resize filter, the algorithm's default when unset.
+    filter: Option<FilterType>,
+    // ahash: subtract the mean before thresholding.
+    center: bool,
+    // phash: how much larger than the hash the DCT input is.
+    highfreq_factor: u32,
+    // phash: leave the DC coefficient out of the hash.
+    drop_dc: bool,
+    // phash: how the DCT is computed.
+    dct_backend: DctBackend,
+    // phash: spread the DCT over the rayon thread pool.
+    parallel_dct: bool,
+    // phash: cosine tables shared between calls, computed on every call when unset.
+    dct_basis: Option<Arc<CosineBasis>>,
+    // phash: number of DCT coefficients kept, in zigzag order.
+    keep: Option<u32>,
+    // phash: how strongly content away from the center is faded out, from 0 to 1.
+    center_weight: Option<f64>,
+    // Center-crop to a square before resizing, instead of squashing the image.
+    crop_to_square: bool,
+    // Sigma of a Gaussian blur applied before resizing, to smooth out noise.
+    blur: Option<f32>,
+    // Compute luminance from linear light instead of the gamma encoded values.
+    linearize_srgb: bool,
+    // The color channels of transparent images are premultiplied by alpha.
+    unpremultiply: bool,
+    // ahash, dhash: bits decided by at most this many gray levels are marked uncertain.
+    uncertain_epsilon: Option<f64>,
+    // dhash: number of gray levels the resized image is quantized to.
+    snap_levels: Option<u32>,
+    // Index of the RGB channel hashed instead of the luminance.
+    channel: Option<usize>,
+    // Tile grid size and clip limit of a CLAHE pass before resizing.
+    clahe: Option<(u32, f64)>,
+    // Crop a near-uniform matte around the content, pixels within this many levels of
+    // its color counting as matte.
+    matte_tolerance: Option<u8>,
+    // Crop to the bounding box of the salient region.
+    saliency_crop: bool,
+    // Rotate slightly tilted content back to the axes first.
+    deskew: bool,
+    // How non-square images are made square before resizing.
+    pad_mode: PadMode,
+    // Accumulates the time spent in `prepare_luma`, when profiling.
+    prepare_time: Option<Arc<Mutex<Duration>>>,
+}
+
+impl Default for HashOptions {
+    fn default() -> Self {
+        HashOptions {
+            background: None,
+            equalize: false,
+            filter: None,
+            center: false,
+            highfreq_factor: 4,
+            drop_dc: true,
+            dct_backend: DctBackend::Separable,
+            parallel_dct: false,
+            dct_basis: None,
+            keep: None,
+            center_weight: None,
+            crop_to_square: false,
+            blur: None,
+            linearize_srgb: false,
+            unpremultiply: false,
+            uncertain_epsilon: None,
+            snap_levels: None,
+            channel: None,
+            clahe: None,
+            matte_tolerance: None,
+            saliency_crop: false,
+            deskew: false,
+            pad_mode: PadMode::Resize,
+            prepare_time: None,
+        }
+    }
+}
+
+// Turns a decoded image into the grayscale grid an algorithm thresholds.
+//
+// A grayscale image and the same image stored as RGB produce exactly the same grid, so
+// their hashes are equal: every channel of a gray RGB pixel is filtered identically, and
+// the luma weights of `to_luma8` sum to one in integer arithmetic, giving back the gray
+// value without rounding. With `linearize_srgb` both go through the same RGB conversion
+// first, and the Rec. 709 weights of a gray pixel add up to exactly one.
+fn prepare_luma(
+    img: DynamicImage,
+    width: u32,
+    height: u32,
+    filter: FilterType,
+    opts: &HashOptions,
+) -> GrayImage {
+    let started = Instant::now();
+    let gray = reduce_to_luma(img, width, height, filter, opts);
+    if let Some(prepare_time) = &opts.prepare_time {
+        *prepare_time.lock().unwrap() += started.elapsed();
+    }
+    gray
+}
+
+// The steps of `prepare_luma`.
+fn reduce_to_luma(
+    img: DynamicImage,
+    width: u32,
+    height: u32,
+    filter: FilterType,
+    opts: &HashOptions,
+) -> GrayImage {
+    // Transparency is flattened so the hash does not depend on hidden color data.
+    let img = if opts.unpremultiply {
+        unpremultiply_alpha(img)
+    } else {
+        img
+    };
+    let img = flatten_alpha(img, opts.background.unwrap_or(DEFAULT_BACKGROUND));
+    let img = match opts.matte_tolerance {
+        Some(tolerance) => remove_matte(img, tolerance),
+        None => img,
+    };
+    let img = if opts.saliency_crop {
+        saliency::saliency_crop(img)
+    } else {
+        img
+    };
+    let img = if opts.deskew { deskew(img) } else { img };
+    let img = if opts.crop_to_square {
+        crop_to_square(img)
+    } else {
+        img
+    };
+    let img = pad_to_square(img, opts.pad_mode);
+    let img = if let Some(channel) = opts.channel {
+        DynamicImage::ImageLuma8(extract_channel(&img, channel))
+    } else if opts.linearize_srgb {
+        DynamicImage::ImageLuma8(linear_luma(&img))
+    } else {
+        img
+    };
+    let img = match opts.blur {
+        Some(sigma) if sigma > 0.0 => img.blur(sigma),
+        _ => img,
+    };
+
+    // Pre-thumbnailed inputs are often already at the target size, and resizing them
+    // would only run the filter to produce the same pixels.
+    let needs_resize = img.dimensions() != (width, height);
+
+    // Equalization needs the full resolution histogram, so it runs before resizing.
+    if opts.equalize || opts.clahe.is_some() {
+        let mut gray = img.to_luma8();
+        if opts.equalize {
+            gray = equalize_histogram(&gray);
+        }
+        if let Some((tiles, clip_limit)) = opts.clahe {
+            gray = clahe(&gray, tiles, clip_limit);
+        }
+        if !needs_resize {
+            return gray;
+        }
+        return imageops::resize(&gray, width, height, filter);
+    }
+
+    if !needs_resize {
+        return img.to_luma8();
+    }
+    img.resize_exact(width, height, filter).to_luma8()
+}
+
+// Relative luminance (Rec. 709 weights) of every pixel, computed after undoing the sRGB
+// transfer function the way photo editors do, and scaled back to 0-255.
+fn linear_luma(img: &DynamicImage) -> GrayImage {
+    let mut linear = [0f64; 256];
+    for (value, l) in linear.iter_mut().enumerate() {
+        let c = value as f64 / 255.0;
+        *l = if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        };
+    }
+
+    let rgb = img.to_rgb8();
+    GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+        let [r, g, b] = rgb.get_pixel(x, y).0;
+        let luma =
+            0.2126 * linear[r as usize] + 0.7152 * linear[g as usize] + 0.0722 * linear[b as usize];
+        image::Luma([(luma * 255.0).round() as u8])
+    })
+}
+
+// A single RGB channel of an image, as a grayscale image.
+fn extract_channel(img: &DynamicImage, channel: usize) -> GrayImage {
+    let rgb = img.to_rgb8();
+    GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+        image::Luma([rgb.get_pixel(x, y).0[channel]])
+    })
+}
+
+// Crops away a matte, the frame of a single near-uniform color that stock photos and
+// product shots often sit on. Its color is taken from the corners, and rows and columns
+// are trimmed from every side while all their pixels stay within `tolerance` levels of
+// it on every channel, so a slightly noisy matte is still recognized. An image that is
+// matte all over is returned untouched.
+fn remove_matte(img: DynamicImage, tolerance: u8) -> DynamicImage {
+    let rgb = img.to_rgb8();
+    let (width, height) = rgb.dimensions();
+    let corners = [
+        rgb.get_pixel(0, 0),
+        rgb.get_pixel(width - 1, 0),
+        rgb.get_pixel(0, height - 1),
+        rgb.get_pixel(width - 1, height - 1),
+    ];
+    // Per channel, the mean of the two middle corners, so one corner covered by the
+    // content does not shift the matte color.
+    let matte: [u8; 3] = std::array::from_fn(|ch| {
+        let mut values = corners.map(|px| px.0[ch]);
+        values.sort_unstable();
+        ((values[1] as u16 + values[2] as u16) / 2) as u8
+    });
+    let is_matte = |x: u32, y: u32| {
+        rgb.get_pixel(x, y)
+            .0
+            .iter()
+            .zip(matte)
+            .all(|(v, m)| v.abs_diff(m) <= tolerance)
+    };
+    let row_is_matte = |y: u32| (0..width).all(|x| is_matte(x, y));
+    let column_is_matte = |x: u32, top: u32, bottom: u32| (top..bottom).all(|y| is_matte(x, y));
+
+    let top = match (0..height).find(|y| !row_is_matte(*y)) {
+        Some(top) => top,
+        None => return img,
+    };
+    let bottom = (top..height)
+        .rev()
+        .find(|y| !row_is_matte(*y))
+        .unwrap_or(top)
+        + 1;
+    let left = (0..width)
+        .find(|x| !column_is_matte(*x, top, bottom))
+        .unwrap_or(0);
+    let right = (left..width)
+        .rev()
+        .find(|x| !column_is_matte(*x, top, bottom))
+        .unwrap_or(left)
+        + 1;
+
+    if (left, top, right, bottom) == (0, 0, width, height) {
+        return img;
+    }
+    img.crop_imm(left, top, right - left, bottom - top)
+}
+
+// Crops the largest centered square out of an image.
+fn crop_to_square(img: DynamicImage) -> DynamicImage {
+    let (width, height) = (img.width(), img.height());
+    let side = width.min(height);
+    img.crop_imm((width - side) / 2, (height - side) / 2, side, side)
+}
+
+// How a non-square image is turned into the square grid hashes are computed on.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PadMode {
+    // Stretch the image to the grid, distorting its aspect ratio.
+    Resize,
+    // Extend the short side with a mirror image of the content next to the border.
+    Reflect,
+    // Extend the short side by repeating the border pixels.
+    Edge,
+}
+
+impl PadMode {
+    fn parse(name: Option<String>) -> PyResult<PadMode> {
+        let name = match name {
+            Some(name) => name,
+            None => return Ok(PadMode::Resize),
+        };
+
+        match name.to_lowercase().as_str() {
+            "resize" => Ok(PadMode::Resize),
+            "reflect" => Ok(PadMode::Reflect),
+            "edge" => Ok(PadMode::Edge),
+            _ => Err(PyValueError::new_err(format!("Unknown pad mode: {}", name))),
+        }
+    }
+}
+
+// Pads the short side of an image so it becomes square, keeping the content centered.
+// With `PadMode::Resize` or an already square image, it is returned untouched.
+fn pad_to_square(img: DynamicImage, mode: PadMode) -> DynamicImage {
+    let (width, height) = (img.width(), img.height());
+    if mode == PadMode::Resize || width == height {
+        return img;
+    }
+
+    let side = width.max(height);
+    let (left, top) = ((side - width) / 2, (side - height) / 2);
+    // Source coordinate along an axis of `len` pixels for a padded coordinate `pos`
+    // lying `offset` pixels after the start of the content.
+    let source = |pos: u32, offset: u32, len: u32| -> u32 {
+        let pos = pos as i64 - offset as i64;
+        let len = len as i64;
+        match mode {
+            PadMode::Edge => pos.clamp(0, len - 1) as u32,
+            // Mirrors around the border pixels without repeating them, over and over
+            // when the padding is wider than the content.
+            _ if len == 1 => 0,
+            _ => {
+                let period = 2 * (len - 1);
+                let pos = pos.rem_euclid(period);
+                (if pos < len { pos } else { period - pos }) as u32
+            }
+        }
+    };
+
+    let rgb = img.to_rgb8();
+    let padded = RgbImage::from_fn(side, side, |x, y| {
+        *rgb.get_pixel(source(x, left, width), source(y, top, height))
+    });
+    DynamicImage::ImageRgb8(padded)
+}
+
+// Spreads the grayscale histogram over the whole 0-255 range, so differently
+// exposed copies of an image threshold alike.
+fn equalize_histogram(img: &GrayImage) -> GrayImage {
+    let mut histogram = [0u64; 256];
+    for px in img.pixels() {
+        histogram[px.0[0] as usize] += 1;
+    }
+
+    let mut cdf = [0u64; 256];
+    let mut running = 0;
+    for (value, count) in histogram.iter().enumerate() {
+        running += count;
+        cdf[value] = running;
+    }
+
+    let total = running;
+    let cdf_min = cdf.iter().copied().find(|c| *c > 0).unwrap_or(0);
+    if total == cdf_min {
+        // Uniform image, there is nothing to spread.
+        return img.clone();
+    }
+
+    let mut equalized = img.clone();
+    for px in equalized.pixels_mut() {
+        let c = cdf[px.0[0] as usize];
+        px.0[0] = ((c - cdf_min) as f64 / (total - cdf_min) as f64 * 255.0).round() as u8;
+    }
+    equalized
+}
+
+// Contrast limited adaptive histogram equalization. Every tile of a `tiles` x `tiles`
+// grid is equalized on its own, with histogram bins capped at `clip_limit` times their
+// average height so noise in flat areas is not blown up. Pixels interpolate between the
+// mappings of the four nearest tiles to avoid seams.
+fn clahe(img: &GrayImage, tiles: u32, clip_limit: f64) -> GrayImage {
+    let (width, height) = img.dimensions();
+    let tiles_x = tiles.min(width) as usize;
+    let tiles_y = tiles.min(height) as usize;
+    let bounds = |len: u32, count: usize, i: usize| {
+        (i * len as usize / count, (i + 1) * len as usize / count)
+    };
+
+    // mappings[ty * tiles_x + tx][value]: equalized value within that tile.
+    let mut mappings = Vec::with_capacity(tiles_x * tiles_y);
+    for ty in 0..tiles_y {
+        for tx in 0..tiles_x {
+            let (x0, x1) = bounds(width, tiles_x, tx);
+            let (y0, y1) = bounds(height, tiles_y, ty);
+            let mut histogram = [0f64; 256];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    histogram[img.get_pixel(x as u32, y as u32).0[0] as usize] += 1.0;
+                }
+            }
+
+            // Clip the bins and hand the excess back evenly to all of them.
+            let count = ((x1 - x0) * (y1 - y0)) as f64;
+            let limit = (clip_limit * count / 256.0).max(1.0);
+            let excess: f64 = histogram.iter().map(|h| (h - limit).max(0.0)).sum();
+            let mut running = 0.0;
+            let mut mapping = [0f64; 256];
+            for (value, h) in histogram.iter().enumerate() {
+                running += h.min(limit) + excess / 256.0;
+                mapping[value] = running / count * 255.0;
+            }
+            mappings.push(mapping);
+        }
+    }
+
+    // Position of a pixel relative to the tile centers along one axis: the two nearest
+    // tiles and the weight of the second one.
+    let neighbors = |pos: u32, len: u32, count: usize| {
+        let center = (pos as f64 + 0.5) * count as f64 / len as f64 - 0.5;
+        let center = center.clamp(0.0, (count - 1) as f64);
+        let first = center.floor() as usize;
+        (first, (first + 1).min(count - 1), center - first as f64)
+    };
+
+    GrayImage::from_fn(width, height, |x, y| {
+        let value = img.get_pixel(x, y).0[0] as usize;
+        let (tx0, tx1, wx) = neighbors(x, width, tiles_x);
+        let (ty0, ty1, wy) = neighbors(y, height, tiles_y);
+        let at = |tx: usize, ty: usize| mappings[ty * tiles_x + tx][value];
+        let top = at(tx0, ty0) * (1.0 - wx) + at(tx1, ty0) * wx;
+        let bottom = at(tx0, ty1) * (1.0 - wx) + at(tx1, ty1) * wx;
+        image::Luma([(top * (1.0 - wy) + bottom * wy).round().min(255.0) as u8])
+    })
+}
+
+// Resolves a resize filter by name.
+fn parse_filter(filter: Option<String>) -> PyResult<Option<FilterType>> {
+    let name = match filter {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+
+    match name.to_lowercase().as_str() {
+        "nearest" => Ok(Some(FilterType::Nearest)),
+        "triangle" => Ok(Some(FilterType::Triangle)),
+        "catmullrom" => Ok(Some(FilterType::CatmullRom)),
+        "gaussian" => Ok(Some(FilterType::Gaussian)),
+        "lanczos3" => Ok(Some(FilterType::Lanczos3)),
+        _ => Err(PyValueError::new_err(format!("Unknown filter: {}", name))),
+    }
+}
+
+// Checks the parameters of a CLAHE pass.
+fn check_clahe(clahe: Option<(u32, f64)>) -> PyResult<Option<(u32, f64)>> {
+    match clahe {
+        Some((tiles, clip_limit)) if tiles == 0 || clip_limit <= 0.0 => Err(PyValueError::new_err(
+            "CLAHE needs at least one tile and a positive clip limit",
+        )),
+        clahe => Ok(clahe),
+    }
+}
+
+// Checks the number of gray levels of a `snap_to_grid` quantization.
+fn check_snap_levels(levels: Option<u32>) -> PyResult<Option<u32>> {
+    match levels {
+        Some(levels) if !(2..=256).contains(&levels) => Err(PyValueError::new_err(
+            "Grid must have between 2 and 256 levels",
+        )),
+        levels => Ok(levels),
+    }
+}
+
+// Tolerance of matte removal when `remove_matte` is set, `DEFAULT_MATTE_TOLERANCE` levels
+// unless overridden.
+fn matte_option(remove_matte: Option<bool>, matte_tolerance: Option<u8>) -> Option<u8> {
+    if remove_matte.unwrap_or(false) {
+        Some(matte_tolerance.unwrap_or(DEFAULT_MATTE_TOLERANCE))
+    } else {
+        None
+    }
+}
+
+// Rejects a hash whose bit entropy is below `min_entropy`, as from a blank or solid image
+// that carries no information worth storing.
+fn check_entropy(hash: ImageHash, min_entropy: Option<f64>) -> PyResult<ImageHash> {
+    match min_entropy {
+        Some(min_entropy) if hash.entropy() < min_entropy => {
+            Err(PyValueError::new_err("image too uniform to hash reliably"))
+        }
+        _ => Ok(hash),
+    }
+}
+
+// Resolves the channel to hash by name, `None` standing for the luminance.
+fn parse_channel(channel: Option<String>) -> PyResult<Option<usize>> {
+    let name = match channel {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+
+    match name.to_lowercase().as_str() {
+        "luma" => Ok(None),
+        "r" => Ok(Some(0)),
+        "g" => Ok(Some(1)),
+        "b" => Ok(Some(2)),
+        _ => Err(PyValueError::new_err(format!("Unknown channel: {}", name))),
+    }
+}
+
+// Rejects an empty hash, which no algorithm can threshold.
+fn check_hash_size(hash_size: u32) -> PyResult<()> {
+    if hash_size == 0 {
+        return Err(PyValueError::new_err("Hash size must be at least 1"));
+    }
+    Ok(())
+}
+
+// Average hash of a decoded image.
+fn average_hash(img: DynamicImage, hash_size: u32, opts: &HashOptions) -> PyResult<ImageHash> {
+    average_hash_rect(img, hash_size, hash_size, opts)
+}
+
+// Number of pixels from which the average hash spreads its work over several threads.
+const PARALLEL_MIN_PIXELS: usize = 64 * 64;
+
+// Average hash over a `width` x `height` grid.
+fn average_hash_rect(
+    img: DynamicImage,
+    width: u32,
+    height: u32,
+    opts: &HashOptions,
+) -> PyResult<ImageHash> {
+    check_hash_size(width)?;
+    check_hash_size(height)?;
+    let filter = opts.filter.unwrap_or(Algorithm::Average.default_filter());
+    let resized = prepare_luma(img, width, height, filter, opts);
+
+    // Large grids are summed and thresholded on the rayon pool. The sum is taken over
+    // integers and every bit only depends on its own pixel, so the result is the same
+    // whatever the number of threads.
+    let pixels: &[u8] = resized.as_raw();
+    let parallel = pixels.len() >= PARALLEL_MIN_PIXELS;
+    let total: u64 = if parallel {
+        pixels.par_iter().map(|v| *v as u64).sum()
+    } else {
+        pixels.iter().map(|v| *v as u64).sum()
+    };
+    let avg = total as f64 / pixels.len() as f64;
+
+    // When centering, the global mean (the DC term) is removed from every pixel first,
+    // so the bits only depend on how each pixel deviates from the overall brightness.
+    let (offset, threshold) = if opts.center { (avg, 0.0) } else { (0.0, avg) };
+
+    let bit = |value: &u8| {
+        let margin = *value as f64 - offset - threshold;
+        let uncertain = opts
+            .uncertain_epsilon
+            .is_some_and(|eps| margin.abs() <= eps);
+        (margin > 0.0, uncertain)
+    };
+    let (bool_result, uncertain): (Vec<bool>, Vec<bool>) = if parallel {
+        pixels.par_iter().map(bit).unzip()
+    } else {
+        pixels.iter().map(bit).unzip()
+    };
+
+    Ok(
+        ImageHash::new_rect(bool_result, width as usize, height as usize)?
+            .with_uncertain(uncertain)
+            .with_algorithm(Algorithm::Average),
+    )
+}
+
+// Perceptual hash of a decoded image.
+fn perceptual_hash(img: DynamicImage, hash_size: u32, opts: &HashOptions) -> PyResult<ImageHash> {
+    check_hash_size(hash_size)?;
+    let filter = opts
+        .filter
+        .unwrap_or(Algorithm::Perceptual.default_filter());
+    let img_size = hash_size * opts.highfreq_factor;
+    let mut resized = prepare_luma(img, img_size, img_size, filter, opts);
+    if let Some(weight) = opts.center_weight {
+        weight_towards_center(&mut resized, weight);
+    }
+    perceptual_hash_luma(&resized, hash_size, opts)
+}
+
+// Fades every pixel towards the image mean by `weight * (r / r_max)^2`, where `r` is its
+// distance from the center. The center keeps its contrast while corners contribute less
+// and less to the hash. A weight of 1 flattens the corners completely.
+fn weight_towards_center(img: &mut GrayImage, weight: f64) {
+    let weight = weight.clamp(0.0, 1.0);
+    let (width, height) = img.dimensions();
+    let mean = img.pixels().map(|px| px.0[0] as f64).sum::<f64>() / (width * height) as f64;
+
+    let (cx, cy) = ((width as f64 - 1.0) / 2.0, (height as f64 - 1.0) / 2.0);
+    let max_dist_sq = (cx.powi(2) + cy.powi(2)).max(1.0);
+    for (x, y, px) in img.enumerate_pixels_mut() {
+        let dist_sq = (x as f64 - cx).powi(2) + (y as f64 - cy).powi(2);
+        let keep = 1.0 - weight * dist_sq / max_dist_sq;
+        px.0[0] = (mean + (px.0[0] as f64 - mean) * keep).round() as u8;
+    }
+}
+
+// Thresholds the low frequency DCT block of an already prepared grayscale image.
+fn perceptual_hash_luma(
+    resized: &GrayImage,
+    hash_size: u32,
+    opts: &HashOptions,
+) -> PyResult<ImageHash> {
+    check_hash_size(hash_size)?;
+    let dct_arr = dct_low_freq(
+        resized,
+        hash_size,
+        opts.dct_backend,
+        opts.parallel_dct,
+        opts.dct_basis.as_deref(),
+    );
+    let positions = coefficient_positions(hash_size, opts.keep)?;
+    let coefs: Vec<f64> = positions.iter().map(|&(v, u)| dct_arr[v][u]).collect();
+
+    // The DC coefficient only carries the overall brightness. When dropped, it is left out
+    // of the threshold and its bit is always unset, so it cannot influence the hash. It
+    // comes first in both orders.
+    let drop_dc = opts.drop_dc;
+    let mut total = coefs.iter().sum::<f64>();
+    let mut count = coefs.len() as f64;
+    if drop_dc {
+        total -= coefs[0];
+        count -= 1.0;
+    }
+    let avg = if count > 0.0 { total / count } else { 0.0 };
+
+    // The backends only agree up to rounding, so a bit is set when its coefficient is
+    // clearly above the mean. Otherwise the rounding noise of a flat image, where every
+    // coefficient but the DC is zero, would decide its bits.
+    let epsilon = 1e-9 * coefs.iter().fold(0.0, |max: f64, c| max.max(c.abs()));
+    let bool_result = coefs
+        .iter()
+        .enumerate()
+        .map(|(c, coef)| !(drop_dc && c == 0) && *coef - avg > epsilon)
+        .collect();
+
+    // A `keep` hash is a single row of bits, the whole block keeps its square shape.
+    let (width, height) = match opts.keep {
+        Some(keep) => (keep as usize, 1),
+        None => (hash_size as usize, hash_size as usize),
+    };
+    Ok(ImageHash::new_rect(bool_result, width, height)?.with_algorithm(Algorithm::Perceptual))
+}
+
+// `(v, u)` position in the DCT block of the coefficient behind each bit of a phash, in
+// bit order. The whole block is used row by row, or with `keep` only that many
+// coefficients, the lowest frequencies first.
+fn coefficient_positions(hash_size: u32, keep: Option<u32>) -> PyResult<Vec<(usize, usize)>> {
+    let size = hash_size as usize;
+    match keep {
+        Some(keep) => {
+            if keep == 0 || keep as usize > size * size {
+                return Err(PyValueError::new_err(
+                    "Keep must be between 1 and hash_size^2",
+                ));
+            }
+            Ok(zigzag(size).take(keep as usize).collect())
+        }
+        None => Ok((0..size)
+            .flat_map(|v| (0..size).map(move |u| (v, u)))
+            .collect()),
+    }
+}
+
+// `(v, u)` indices of a `size` x `size` block in JPEG zigzag order, walking the
+// anti-diagonals from the lowest frequencies to the highest.
+fn zigzag(size: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..2 * size - 1).flat_map(move |s| {
+        let first = s.saturating_sub(size - 1);
+        let last = s.min(size - 1);
+        (first..=last).map(move |i| if s % 2 == 0 { (s - i, i) } else { (i, s - i) })
+    })
+}
+
+// Replaces pixels that deviate from their neighborhood median by more than `tolerance`
+// with that median. Thin high contrast strokes such as overlaid text are removed while
+// smooth image content is left untouched.
+fn suppress_spikes(img: &GrayImage, radius: u32, tolerance: u8) -> GrayImage {
+    let (width, height) = img.dimensions();
+    let mut cleaned = img.clone();
+    let mut window: Vec<f64> = Vec::with_capacity(((2 * radius + 1) as usize).pow(2));
+
+    for y in 0..height {
+        for x in 0..width {
+            window.clear();
+            for ny in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+                for nx in x.saturating_sub(radius)..(x + radius + 1).min(width) {
+                    window.push(img.get_pixel(nx, ny).0[0] as f64);
+                }
+            }
+
+            let local = median(&mut window);
+            let value = img.get_pixel(x, y).0[0] as f64;
+            if (value - local).abs() > tolerance as f64 {
+                cleaned.get_pixel_mut(x, y).0[0] = local.round() as u8;
+            }
+        }
+    }
+    cleaned
+}
+
+// Median of the values, found with quickselect in O(n) instead of a full sort.
+// Even-sized inputs average the two middle values.
+fn median(values: &mut [f64]) -> f64 {
+    let len = values.len();
+    let (lower, upper, _) = values.select_nth_unstable_by(len / 2, |a, b| a.total_cmp(b));
+    let upper = *upper;
+    if len % 2 == 1 {
+        return upper;
+    }
+
+    let lower = lower.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    (lower + upper) / 2.0
+}
+
+// Median hash of a decoded image, thresholding every pixel against the median.
+fn median_hash(img: DynamicImage, hash_size: u32, opts: &HashOptions) -> PyResult<ImageHash> {
+    check_hash_size(hash_size)?;
+    let filter = opts.filter.unwrap_or(Algorithm::Median.default_filter());
+    let resized = prepare_luma(img, hash_size, hash_size, filter, opts);
+
+    let mut pixels: Vec<f64> = resized.pixels().map(|px| px.0[0] as f64).collect();
+    let threshold = median(&mut pixels);
+
+    let bool_result = resized
+        .pixels()
+        .map(|px| px.0[0] as f64 > threshold)
+        .collect();
+    Ok(ImageHash::new(bool_result, hash_size as usize)?.with_algorithm(Algorithm::Median))
+}
+
+// Difference hash of a decoded image.
+fn difference_hash(img: DynamicImage, hash_size: u32, opts: &HashOptions) -> PyResult<ImageHash> {
+    check_hash_size(hash_size)?;
+    let resized = difference_grid(img, hash_size, opts);
+    difference_hash_grid(&resized, hash_size, false, opts)
+}
+
+// The `hash_size + 1` square grayscale grid a difference hash compares neighbors in.
+fn difference_grid(img: DynamicImage, hash_size: u32, opts: &HashOptions) -> GrayImage {
+    let filter = opts
+        .filter
+        .unwrap_or(Algorithm::Difference.default_filter());
+    let mut resized = prepare_luma(img, hash_size + 1, hash_size + 1, filter, opts);
+    if let Some(levels) = opts.snap_levels {
+        snap_to_levels(&mut resized, levels);
+    }
+    resized
+}
+
+// Difference hash of a grid from `difference_grid`, each bit set when a pixel is brighter
+// than its right neighbor, or with `vertical` than the one below it.
+fn difference_hash_grid(
+    resized: &GrayImage,
+    hash_size: u32,
+    vertical: bool,
+    opts: &HashOptions,
+) -> PyResult<ImageHash> {
+    let hashpow = hash_size.pow(2);
+    let mut bool_result = vec![false; hashpow as usize];
+    let mut uncertain = vec![false; hashpow as usize];
+    let (dx, dy) = if vertical { (0, 1) } else { (1, 0) };
+
+    let mut y = 0;
+    while y < hash_size {
+        let mut x = 0;
+        while x < hash_size {
+            let c = (y * hash_size + x) as usize;
+            let pixel = resized.get_pixel(x, y).0[0];
+            let neighbor = resized.get_pixel(x + dx, y + dy).0[0];
+
+            let cmp = pixel > neighbor;
+            bool_result[c] = cmp;
+            uncertain[c] = opts
+                .uncertain_epsilon
+                .is_some_and(|eps| pixel.abs_diff(neighbor) as f64 <= eps);
+
+            x += 1;
+        }
+        y += 1;
+    }
+
+    Ok(ImageHash::new(bool_result, hash_size as usize)?
+        .with_uncertain(uncertain)
+        .with_algorithm(Algorithm::Difference))
+}
+
+// Quantizes every pixel to one of `levels` evenly spaced gray levels. Neighbors that
+// only differ by resampling noise end up equal, and equal pixels never set a dhash bit.
+fn snap_to_levels(img: &mut GrayImage, levels: u32) {
+    let step = 256.0 / levels as f64;
+    for px in img.pixels_mut() {
+        px.0[0] = ((px.0[0] as f64 / step).floor() * step) as u8;
+    }
+}
+
+// Size of a hash grid given from Python, a single side or a `(width, height)` tuple.
+#[derive(FromPyObject)]
+enum HashSize {
+    Square(u32),
+    Rect((u32, u32)),
+}
+
+impl HashSize {
+    fn dimensions(&self) -> (u32, u32) {
+        match *self {
+            HashSize::Square(side) => (side, side),
+            HashSize::Rect(dimensions) => dimensions,
+        }
+    }
+}
+
+// Hashes an image using average hash
+//
+// The image is resized with a triangle filter by default: averaging washes out the
+// detail a sharper filter would preserve, so the cheaper filter is good enough.
+// `hash_size` is either the side of a square grid or a `(width, height)` tuple, for
+// example `(16, 9)` to fit wide content.
+//
+// A non-square image is stretched to the grid unless `pad_mode` is "reflect" or "edge",
+// which pad its short side to a square first, mirroring or repeating the border pixels.
+// This avoids the distortion of near-square thumbnails. The default is "resize".
+// With `remove_matte`, a near-uniform frame around the content is cropped first, so the
+// same product shot on mattes of different widths matches. `matte_tolerance` sets how
+// noisy the matte may be, 16 gray levels by default. With `saliency_crop`, only the
+// bounding box of the subject found by a spectral residual saliency map is hashed, so the
+// same subject on different backgrounds matches.
+// Like every hash, it is computed from the stored pixel values: embedded ICC color
+// profiles are never applied.
+// The GIL is released while hashing, and large grids are processed on several threads.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn ahash(
+    py: Python<'_>,
+    fpath: String,
+    hash_size: HashSize,
+    background: Option<(u8, u8, u8)>,
+    center: Option<bool>,
+    filter: Option<String>,
+    equalize: Option<bool>,
+    crop_to_square: Option<bool>,
+    strict: Option<bool>,
+    linearize_srgb: Option<bool>,
+    unpremultiply: Option<bool>,
+    uncertain_epsilon: Option<f64>,
+    channel: Option<String>,
+    clahe: Option<(u32, f64)>,
+    page: Option<u32>,
+    profile: Option<bool>,
+    min_entropy: Option<f64>,
+    auto_deskew: Option<bool>,
+    pad_mode: Option<String>,
+    remove_matte: Option<bool>,
+    matte_tolerance: Option<u8>,
+    saliency_crop: Option<bool>,
+) -> PyResult<PyObject> {
+    let mut opts = HashOptions {
+        background,
+        center: center.unwrap_or(false),
+        filter: parse_filter(filter)?,
+        equalize: equalize.unwrap_or(false),
+        crop_to_square: crop_to_square.unwrap_or(false),
+        linearize_srgb: linearize_srgb.unwrap_or(false),
+        unpremultiply: unpremultiply.unwrap_or(false),
+        uncertain_epsilon,
+        channel: parse_channel(channel)?,
+        clahe: check_clahe(clahe)?,
+        deskew: auto_deskew.unwrap_or(false),
+        pad_mode: PadMode::parse(pad_mode)?,
+        matte_tolerance: matte_option(remove_matte, matte_tolerance),
+        saliency_crop: saliency_crop.unwrap_or(false),
+        ..Default::default()
+    };
+    let (width, height) = hash_size.dimensions();
+    let mut profiler = Profiler::start(profile, &mut opts);
+    let hash = py.allow_threads(|| {
+        let img = open_image_with(&fpath, strict.unwrap_or(false), page.unwrap_or(0))?;
+        profiler.decoded();
+        average_hash_rect(img, width, height, &opts)
+    })?;
+    let hash = check_entropy(hash, min_entropy)?;
+    Ok(profiler.finish(py, hash))
+}
+
+// Hashes an image using median hash
+//
+// Like average hash, but thresholds against the median so that each bit is set for
+// about half of the pixels regardless of outliers.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn mhash(
+    py: Python<'_>,
+    fpath: String,
+    hash_size: u32,
+    background: Option<(u8, u8, u8)>,
+    filter: Option<String>,
+    strict: Option<bool>,
+    linearize_srgb: Option<bool>,
+    unpremultiply: Option<bool>,
+    channel: Option<String>,
+    page: Option<u32>,
+    profile: Option<bool>,
+    min_entropy: Option<f64>,
+    auto_deskew: Option<bool>,
+    pad_mode: Option<String>,
+    remove_matte: Option<bool>,
+    matte_tolerance: Option<u8>,
+    saliency_crop: Option<bool>,
+) -> PyResult<PyObject> {
+    let mut opts = HashOptions {
+        background,
+        filter: parse_filter(filter)?,
+        linearize_srgb: linearize_srgb.unwrap_or(false),
+        unpremultiply: unpremultiply.unwrap_or(false),
+        channel: parse_channel(channel)?,
+        deskew: auto_deskew.unwrap_or(false),
+        pad_mode: PadMode::parse(pad_mode)?,
+        matte_tolerance: matte_option(remove_matte, matte_tolerance),
+        saliency_crop: saliency_crop.unwrap_or(false),
+        ..Default::default()
+    };
+    let mut profiler = Profiler::start(profile, &mut opts);
+    let img = open_image_with(&fpath, strict.unwrap_or(false), page.unwrap_or(0))?;
+    profiler.decoded();
+    let hash = median_hash(img, hash_size, &opts)?;
+    let hash = check_entropy(hash, min_entropy)?;
+    Ok(profiler.finish(py, hash))
+}
+
+// Hashes an image using a perceptual hash that tolerates text overlays
+//
+// Before the DCT, the image is median filtered wherever a pixel sharply deviates from
+// its surroundings, which is where captions and watermarks live. A captioned copy of an
+// image therefore stays closer to the original than under plain `phash`.
+#[pyfunction]
+fn text_robust_hash(fpath: String, hash_size: u32) -> PyResult<ImageHash> {
+    let opts = HashOptions::default();
+    let img_size = hash_size * opts.highfreq_factor;
+
+    // Filter close to the source resolution, where strokes are still thin relative to the
+    // window. Very large images are scaled down first to bound the cost.
+    let img = open_image(&fpath)?;
+    let scale = (TEXT_FILTER_MAX_SIDE as f64 / img.width().max(img.height()) as f64).min(1.0);
+    let width = ((img.width() as f64 * scale).round() as u32).max(1);
+    let height = ((img.height() as f64 * scale).round() as u32).max(1);
+    let detailed = prepare_luma(
+        img,
+        width,
+        height,
+        Algorithm::Perceptual.default_filter(),
+        &opts,
+    );
+    let cleaned = suppress_spikes(&detailed, 2, 40);
+    let resized = imageops::resize(
+        &cleaned,
+        img_size,
+        img_size,
+        Algorithm::Perceptual.default_filter(),
+    );
+
+    perceptual_hash_luma(&resized, hash_size, &opts)
+}
+
+// Hashes an image into 2 bits per cell instead of 1
+//
+// Each pixel of the `hash_size` x `hash_size` grayscale thumbnail is graded 0 to 3 by how
+// many of the quartiles it exceeds, so the hash keeps some of the magnitude thresholding
+// against the median alone throws away. The level of pixel `(x, y)` is stored as two bits,
+// high bit first, at `(2x, y)` and `(2x + 1, y)` of a `2 * hash_size` x `hash_size` grid,
+// while `hash_size` still reports the side of the cell grid. Compare such hashes with
+// `quad_distance`.
+#[pyfunction]
+fn quad_hash(fpath: String, hash_size: u32) -> PyResult<ImageHash> {
+    Algorithm::Quad.hash(&fpath, hash_size)
+}
+
+// Quad hash of a decoded image, see `quad_hash`.
+fn quad_hash_image(img: DynamicImage, hash_size: u32, opts: &HashOptions) -> PyResult<ImageHash> {
+    if hash_size < 2 {
+        return Err(PyValueError::new_err("Hash size must be at least 2"));
+    }
+
+    let filter = opts.filter.unwrap_or(Algorithm::Quad.default_filter());
+    let resized = prepare_luma(img, hash_size, hash_size, filter, opts);
+
+    let pixels: Vec<f64> = resized.pixels().map(|px| px.0[0] as f64).collect();
+    let mut sorted = pixels.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let half = sorted.len() / 2;
+    let upper = sorted.len() - half;
+    let quartiles = [
+        median(&mut sorted[..half].to_vec()),
+        median(&mut sorted.clone()),
+        median(&mut sorted[upper..].to_vec()),
+    ];
+
+    let bool_result = pixels
+        .iter()
+        .flat_map(|value| {
+            let level = quartiles.iter().filter(|q| value > q).count();
+            [level >= 2, level % 2 == 1]
+        })
+        .collect();
+    let (width, height) = Algorithm::Quad.bit_grid(hash_size as usize);
+    Ok(ImageHash::new_rect(bool_result, width, height)?.with_algorithm(Algorithm::Quad))
+}
+
+// Distance between two `quad_hash` hashes, the sum over all cells of how many levels
+// apart they are. Unlike the Hamming distance of the bits, a one level change always
+// counts 1.
+#[pyfunction]
+fn quad_distance(a: &ImageHash, b: &ImageHash) -> PyResult<u32> {
+    let is_quad = |hash: &ImageHash| matches!(hash.algorithm, Some(Algorithm::Quad));
+    if !is_quad(a) || !is_quad(b) {
+        return Err(PyValueError::new_err("Not a quad hash"));
+    }
+    a.check_shape(b)?;
+
+    let level = |bits: &[bool]| 2 * bits[0] as i32 + bits[1] as i32;
+    Ok(a.bool_values
+        .chunks(2)
+        .zip(b.bool_values.chunks(2))
+        .map(|(x, y)| level(x).abs_diff(level(y)))
+        .sum())
+}
+
+// Hashes the alpha channel of an image instead of its luminance
+//
+// The alpha is reduced to `hash_size` x `hash_size` and thresholded against its mean like
+// `ahash`, so icons with the same silhouette match whatever their fill. Images without an
+// alpha channel are rejected.
+#[pyfunction]
+fn alpha_hash(fpath: String, hash_size: u32) -> PyResult<ImageHash> {
+    let img = open_image(&fpath)?;
+    if !img.color().has_alpha() {
+        return Err(PyValueError::new_err("Image has no alpha channel"));
+    }
+
+    let rgba = img.to_rgba8();
+    let alpha = GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        image::Luma([rgba.get_pixel(x, y).0[3]])
+    });
+    let mut hash = average_hash(
+        DynamicImage::ImageLuma8(alpha),
+        hash_size,
+        &HashOptions::default(),
+    )?;
+    // The bits are not comparable with an ahash of the same image.
+    hash.algorithm = None;
+    Ok(hash)
+}
+
+// Encodes an image into a ThumbHash blob
+//
+// Unlike the other hashes this is not meant for comparisons: the couple dozen bytes can be
+// decoded by any ThumbHash implementation into a blurry preview, to show as a placeholder
+// while the full image loads.
+#[pyfunction]
+fn thumbhash(fpath: String) -> PyResult<Vec<u8>> {
+    Ok(thumb::encode(&open_image(&fpath)?))
+}
+
+// Hashes an image using average hash without blocking the asyncio event loop.
+//
+// Decoding and hashing run on tokio's blocking thread pool, and the returned awaitable
+// resolves with the same `ImageHash` the synchronous `ahash` would produce.
+#[pyfunction]
+fn ahash_async(py: Python<'_>, fpath: String, hash_size: u32) -> PyResult<&PyAny> {
+    check_hash_size(hash_size)?;
+    pyo3_asyncio::tokio::future_into_py(py, async move {
+        match tokio::task::spawn_blocking(move || Algorithm::Average.hash(&fpath, hash_size)).await
+        {
+            Ok(result) => result,
+            Err(_e) => Err(PyValueError::new_err("Hashing task failed.")),
+        }
+    })
+}
+
+// Average hash thresholded by a Python callable instead of the mean. `threshold_fn` is
+// called as `threshold_fn(mean, pixels)` with the mean gray level and the resized pixels
+// row by row, and returns the threshold: bits are set for pixels above it.
+#[pyfunction]
+fn ahash_custom(
+    py: Python<'_>,
+    fpath: String,
+    hash_size: u32,
+    threshold_fn: PyObject,
+) -> PyResult<ImageHash> {
+    check_hash_size(hash_size)?;
+    let opts = HashOptions::default();
+    let resized = Algorithm::Average.grid(open_image(&fpath)?, hash_size, &opts);
+    let pixels = resized.into_raw();
+    let mean = pixels.iter().map(|v| *v as f64).sum::<f64>() / pixels.len() as f64;
+
+    let threshold: f64 = threshold_fn
+        .call1(py, (mean, pixels.clone()))?
+        .extract(py)?;
+    let bool_result = pixels.iter().map(|v| *v as f64 > threshold).collect();
+    Ok(ImageHash::new(bool_result, hash_size as usize)?.with_algorithm(Algorithm::Average))
+}
+
+// Average hash of a thumbnail already reduced elsewhere, such as on a GPU. `data` must be
+// exactly `size * size` luminance bytes, row by row, and is thresholded as is without any
+// resizing.
+#[pyfunction]
+fn ahash_from_grayscale_buffer(data: &[u8], size: u32) -> PyResult<ImageHash> {
+    let thumbnail = match GrayImage::from_raw(size, size, data.to_vec()) {
+        Some(thumbnail) if data.len() == (size * size) as usize && size > 0 => thumbnail,
+        _ => {
+            return Err(PyValueError::new_err(
+                "Buffer must hold exactly size * size bytes",
+            ))
+        }
+    };
+    average_hash(
+        DynamicImage::ImageLuma8(thumbnail),
+        size,
+        &HashOptions::default(),
+    )
+}
+
+// Average hash of a grayscale image given as a list of rows of 0-255 values, resized to
+// `hash_size` x `hash_size` like a decoded file would be. Every row must have the same
+// length.
+#[pyfunction]
+fn ahash_from_matrix(matrix: Vec<Vec<u8>>, hash_size: u32) -> PyResult<ImageHash> {
+    let width = matrix.first().map_or(0, Vec::len);
+    if width == 0 || matrix.iter().any(|row| row.len() != width) {
+        return Err(PyValueError::new_err(
+            "Matrix must be non-empty with rows of equal length",
+        ));
+    }
+
+    let height = matrix.len() as u32;
+    let pixels = matrix.concat();
+    let img = match GrayImage::from_raw(width as u32, height, pixels) {
+        Some(img) => img,
+        None => return Err(PyValueError::new_err("Matrix is too large")),
+    };
+    average_hash(
+        DynamicImage::ImageLuma8(img),
+        hash_size,
+        &HashOptions::default(),
+    )
+}
+
+// Average hash of an image held as a `(height, width, 3)` array of 8-bit samples, such
+// as a numpy array, read through the buffer protocol. OpenCV arrays store channels in BGR
+// order, which `bgr` swaps back to RGB so luminance is computed with the right weights.
+#[pyfunction]
+fn ahash_from_ndarray(
+    py: Python<'_>,
+    arr: PyBuffer<u8>,
+    hash_size: u32,
+    bgr: bool,
+) -> PyResult<ImageHash> {
+    let (height, width) = match arr.shape() {
+        [height, width, 3] if *height > 0 && *width > 0 => (*height as u32, *width as u32),
+        _ => {
+            return Err(PyValueError::new_err(
+                "Array must have a (height, width, 3) shape",
+            ))
+        }
+    };
+
+    let mut samples = arr.to_vec(py)?;
+    if bgr {
+        for px in samples.chunks_exact_mut(3) {
+            px.swap(0, 2);
+        }
+    }
+    let img = match RgbImage::from_raw(width, height, samples) {
+        Some(img) => img,
+        None => return Err(PyValueError::new_err("Array is too large")),
+    };
+    average_hash(
+        DynamicImage::ImageRgb8(img),
+        hash_size,
+        &HashOptions::default(),
+    )
+}
+
+// Hashes an image using perceptual hash
+//
+// The image is resized with Lanczos3 by default so the DCT sees accurate frequencies.
+// The DC coefficient is dropped unless `drop_dc` is false. The DCT `backend` is one of
+// "naive", "separable" (default) or "fft" (requires the `fft` feature), and all of them
+// produce the same bits. A `center_weight` between 0 and 1 emphasizes the center of the
+// image, where the subject usually is, over its corners. With `parallel`, the DCT of a
+// large hash is spread over several threads. The GIL is released while hashing.
+//
+// By default the whole `hash_size` x `hash_size` block of coefficients is used. With
+// `keep`, only the first `keep` of them in zigzag order are, so a larger DCT can be
+// sampled for a shorter hash. The hash is then a single row of `keep` bits.
+//
+// The only resize is the one to the `hash_size * highfreq_factor` DCT input, which
+// dominates the cost for large factors. `intermediate_filter` sets its filter, taking
+// precedence over `filter`: a cheap "triangle" there barely moves the low frequency
+// coefficients the hash keeps.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn phash(
+    py: Python<'_>,
+    fpath: String,
+    hash_size: u32,
+    highfreq_factor: u32,
+    background: Option<(u8, u8, u8)>,
+    filter: Option<String>,
+    drop_dc: Option<bool>,
+    equalize: Option<bool>,
+    crop_to_square: Option<bool>,
+    backend: Option<String>,
+    center_weight: Option<f64>,
+    parallel: Option<bool>,
+    keep: Option<u32>,
+    strict: Option<bool>,
+    linearize_srgb: Option<bool>,
+    unpremultiply: Option<bool>,
+    channel: Option<String>,
+    clahe: Option<(u32, f64)>,
+    page: Option<u32>,
+    profile: Option<bool>,
+    min_entropy: Option<f64>,
+    auto_deskew: Option<bool>,
+    pad_mode: Option<String>,
+    remove_matte: Option<bool>,
+    matte_tolerance: Option<u8>,
+    intermediate_filter: Option<String>,
+    saliency_crop: Option<bool>,
+) -> PyResult<PyObject> {
+    let mut opts = HashOptions {
+        background,
+        highfreq_factor,
+        filter: parse_filter(intermediate_filter)?.or(parse_filter(filter)?),
+        drop_dc: drop_dc.unwrap_or(true),
+        dct_backend: DctBackend::parse(backend)?,
+        center_weight,
+        parallel_dct: parallel.unwrap_or(false),
+        keep,
+        equalize: equalize.unwrap_or(false),
+        crop_to_square: crop_to_square.unwrap_or(false),
+        linearize_srgb: linearize_srgb.unwrap_or(false),
+        unpremultiply: unpremultiply.unwrap_or(false),
+        channel: parse_channel(channel)?,
+        clahe: check_clahe(clahe)?,
+        deskew: auto_deskew.unwrap_or(false),
+        pad_mode: PadMode::parse(pad_mode)?,
+        matte_tolerance: matte_option(remove_matte, matte_tolerance),
+        saliency_crop: saliency_crop.unwrap_or(false),
+        ..Default::default()
+    };
+    let mut profiler = Profiler::start(profile, &mut opts);
+    let hash = py.allow_threads(|| {
+        let img = open_image_with(&fpath, strict.unwrap_or(false), page.unwrap_or(0))?;
+        profiler.decoded();
+        perceptual_hash(img, hash_size, &opts)
+    })?;
+    let hash = check_entropy(hash, min_entropy)?;
+    Ok(profiler.finish(py, hash))
+}
+
+// Perceptual hash with default options, along with the `(v, u)` position in the DCT block
+// of the coefficient behind each bit, in bit order: `v` is the vertical frequency and
+// `u` the horizontal one. The DC coefficient at `(0, 0)` is dropped, so the first bit it
+// would decide is always unset and it is left out of the positions: there is one
+// position less than bits, and `positions[i]` is behind bit `i + 1`.
+#[pyfunction]
+fn phash_debug(
+    fpath: String,
+    hash_size: u32,
+    highfreq_factor: u32,
+) -> PyResult<(ImageHash, Vec<(u32, u32)>)> {
+    let opts = HashOptions {
+        highfreq_factor,
+        ..Default::default()
+    };
+    let hash = perceptual_hash(open_image(&fpath)?, hash_size, &opts)?;
+    let positions = coefficient_positions(hash_size, opts.keep)?;
+    let positions = positions
+        .into_iter()
+        .filter(|&position| position != (0, 0))
+        .map(|(v, u)| (v as u32, u as u32))
+        .collect();
+    Ok((hash, positions))
+}
+
+// Hashes an image using the log-magnitude of its Fourier spectrum instead of its DCT
+//
+// The magnitude spectrum ignores where a pattern sits, so two tilings of one texture with
+// the same period but shifted relative to each other hash closely, where `phash` sees
+// different images. Each of the `hash_size` x `hash_size` low frequency magnitudes is
+// compared to their median, and the DC term's bit is always unset. The hash counts as a
+// phash. Requires the `fft` feature.
+#[pyfunction]
+fn phash_spectral(fpath: String, hash_size: u32) -> PyResult<ImageHash> {
+    check_hash_size(hash_size)?;
+    let opts = HashOptions::default();
+    let img_size = hash_size * opts.highfreq_factor;
+    let resized = prepare_luma(
+        open_image(&fpath)?,
+        img_size,
+        img_size,
+        Algorithm::Perceptual.default_filter(),
+        &opts,
+    );
+    let magnitudes = spectrum::log_magnitude(&resized, hash_size)?;
+
+    let dc = (hash_size / 2 * hash_size) as usize;
+    let mut others: Vec<f64> = magnitudes
+        .iter()
+        .enumerate()
+        .filter(|(c, _)| *c != dc)
+        .map(|(_, m)| *m)
+        .collect();
+    let threshold = if others.is_empty() {
+        0.0
+    } else {
+        median(&mut others)
+    };
+
+    let bool_result = magnitudes
+        .iter()
+        .enumerate()
+        .map(|(c, m)| c != dc && *m > threshold)
+        .collect();
+    Ok(ImageHash::new(bool_result, hash_size as usize)?.with_algorithm(Algorithm::Perceptual))
+}
+
+// Hashes an image using difference hash
+//
+// The image is resized with Lanczos3 by default, as its sharper kernel keeps the
+// gradients between neighboring pixels that the hash compares. Since those comparisons
+// are sensitive to single pixel noise, a Gaussian `blur` can be applied beforehand.
+// Screenshots taken at different scales differ by subpixel resampling artifacts, which
+// `snap_to_grid` hides by quantizing the resized image to that many gray levels.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn dhash(
+    py: Python<'_>,
+    fpath: String,
+    hash_size: u32,
+    background: Option<(u8, u8, u8)>,
+    filter: Option<String>,
+    equalize: Option<bool>,
+    crop_to_square: Option<bool>,
+    blur: Option<f32>,
+    strict: Option<bool>,
+    linearize_srgb: Option<bool>,
+    unpremultiply: Option<bool>,
+    uncertain_epsilon: Option<f64>,
+    snap_to_grid: Option<u32>,
+    channel: Option<String>,
+    clahe: Option<(u32, f64)>,
+    page: Option<u32>,
+    profile: Option<bool>,
+    min_entropy: Option<f64>,
+    auto_deskew: Option<bool>,
+    pad_mode: Option<String>,
+    remove_matte: Option<bool>,
+    matte_tolerance: Option<u8>,
+    saliency_crop: Option<bool>,
+) -> PyResult<PyObject> {
+    let mut opts = HashOptions {
+        background,
+        filter: parse_filter(filter)?,
+        equalize: equalize.unwrap_or(false),
+        crop_to_square: crop_to_square.unwrap_or(false),
+        blur,
+        linearize_srgb: linearize_srgb.unwrap_or(false),
+        unpremultiply: unpremultiply.unwrap_or(false),
+        uncertain_epsilon,
+        snap_levels: check_snap_levels(snap_to_grid)?,
+        channel: parse_channel(channel)?,
+        clahe: check_clahe(clahe)?,
+        deskew: auto_deskew.unwrap_or(false),
+        pad_mode: PadMode::parse(pad_mode)?,
+        matte_tolerance: matte_option(remove_matte, matte_tolerance),
+        saliency_crop: saliency_crop.unwrap_or(false),
+        ..Default::default()
+    };
+    let mut profiler = Profiler::start(profile, &mut opts);
+    let img = open_image_with(&fpath, strict.unwrap_or(false), page.unwrap_or(0))?;
+    profiler.decoded();
+    let hash = difference_hash(img, hash_size, &opts)?;
+    let hash = check_entropy(hash, min_entropy)?;
+    Ok(profiler.finish(py, hash))
+}
+
+// Difference hashes of an image along both directions from a single decode, as
+// `(horizontal, vertical)`. The horizontal hash is the one `dhash` gives, the vertical
+// one compares each pixel to the one below it instead.
+#[pyfunction]
+fn dhash_pair(fpath: String, hash_size: u32) -> PyResult<(ImageHash, ImageHash)> {
+    check_hash_size(hash_size)?;
+    let opts = HashOptions::default();
+    let resized = difference_grid(open_image(&fpath)?, hash_size, &opts);
+    Ok((
+        difference_hash_grid(&resized, hash_size, false, &opts)?,
+        difference_hash_grid(&resized, hash_size, true, &opts)?,
+    ))
+}
+
+// The grayscale grid `algo` thresholds for a file with default options, as a
+// `(height, width)` numpy array of `uint8`: `hash_size` square for ahash, mhash and quad,
+// `hash_size + 1` for dhash and `4 * hash_size` for the DCT input of phash. numpy is only
+// imported here, the rest of the module works without it.
+#[pyfunction]
+fn grayscale_array(
+    py: Python<'_>,
+    fpath: String,
+    hash_size: u32,
+    algo: String,
+) -> PyResult<PyObject> {
+    check_hash_size(hash_size)?;
+    let algo = Algorithm::parse(&algo)?;
+    let numpy = match py.import("numpy") {
+        Ok(numpy) => numpy,
+        Err(_e) => return Err(PyImportError::new_err("grayscale_array requires numpy")),
+    };
+    let grid = py.allow_threads(|| -> PyResult<GrayImage> {
+        Ok(algo.grid(open_image(&fpath)?, hash_size, &HashOptions::default()))
+    })?;
+
+    // The bytearray is writable, so unlike one over `bytes` the array is too.
+    let (width, height) = grid.dimensions();
+    let buffer = PyByteArray::new(py, grid.as_raw());
+    let array = numpy
+        .call_method1("frombuffer", (buffer, numpy.getattr("uint8")?))?
+        .call_method1("reshape", ((height, width),))?;
+    Ok(array.into())
+}
+
+// Hashing algorithms selectable by name from the batch helpers.
+#[derive(Clone, Copy)]
+enum Algorithm {
+    Average,
+    Difference,
+    Perceptual,
+    Median,
+    Quad,
+}
+
+impl Algorithm {
+    // The algorithms with one bit per cell.
+    const ALL: [Algorithm; 4] = [
+        Algorithm::Average,
+        Algorithm::Difference,
+        Algorithm::Perceptual,
+        Algorithm::Median,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Average => "ahash",
+            Algorithm::Difference => "dhash",
+            Algorithm::Perceptual => "phash",
+            Algorithm::Median => "mhash",
+            Algorithm::Quad => "quad",
+        }
+    }
+
+    fn parse(name: &str) -> PyResult<Algorithm> {
+        match name.to_lowercase().as_str() {
+            "ahash" => Ok(Algorithm::Average),
+            "dhash" => Ok(Algorithm::Difference),
+            "phash" => Ok(Algorithm::Perceptual),
+            "mhash" => Ok(Algorithm::Median),
+            "quad" => Ok(Algorithm::Quad),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown algorithm: {}",
+                name
+            ))),
+        }
+    }
+
+    // `(midpoint, steepness)` of the logistic curve turning a normalized distance into a
+    // match probability. The midpoint sits where same and different image pairs overlap:
+    // the gradient based hashes flip more bits under harmless edits than the mean based
+    // ones do.
+    fn calibration(self) -> (f64, f64) {
+        match self {
+            Algorithm::Average | Algorithm::Median | Algorithm::Quad => (0.15, 40.0),
+            Algorithm::Difference => (0.2, 35.0),
+            Algorithm::Perceptual => (0.25, 30.0),
+        }
+    }
+
+    // `(width, height)` of the bit grid of a `hash_size` hash, wider for the two bits per
+    // cell of a quad hash.
+    fn bit_grid(self, hash_size: usize) -> (usize, usize) {
+        match self {
+            Algorithm::Quad => (2 * hash_size, hash_size),
+            _ => (hash_size, hash_size),
+        }
+    }
+
+    fn default_filter(self) -> FilterType {
+        match self {
+            Algorithm::Average | Algorithm::Median | Algorithm::Quad => FilterType::Triangle,
+            Algorithm::Difference | Algorithm::Perceptual => FilterType::Lanczos3,
+        }
+    }
+
+    // Hashes a decoded image.
+    fn hash_image(
+        self,
+        img: DynamicImage,
+        hash_size: u32,
+        opts: &HashOptions,
+    ) -> PyResult<ImageHash> {
+        match self {
+            Algorithm::Average => average_hash(img, hash_size, opts),
+            Algorithm::Difference => difference_hash(img, hash_size, opts),
+            Algorithm::Perceptual => perceptual_hash(img, hash_size, opts),
+            Algorithm::Median => median_hash(img, hash_size, opts),
+            Algorithm::Quad => quad_hash_image(img, hash_size, opts),
+        }
+    }
+
+    // The grayscale grid the algorithm computes its bits from, before any thresholding.
+    fn grid(self, img: DynamicImage, hash_size: u32, opts: &HashOptions) -> GrayImage {
+        let filter = opts.filter.unwrap_or(self.default_filter());
+        match self {
+            Algorithm::Average | Algorithm::Median | Algorithm::Quad => {
+                prepare_luma(img, hash_size, hash_size, filter, opts)
+            }
+            Algorithm::Difference => difference_grid(img, hash_size, opts),
+            Algorithm::Perceptual => {
+                let img_size = hash_size * opts.highfreq_factor;
+                prepare_luma(img, img_size, img_size, filter, opts)
+            }
+        }
+    }
+
+    // Hashes a file with the algorithm's default options.
+    fn hash(self, fpath: &str, hash_size: u32) -> PyResult<ImageHash> {
+        self.hash_image(open_image(fpath)?, hash_size, &HashOptions::default())
+    }
+
+    // Packed bytes of the hash of a file, without handing an `ImageHash` to Python.
+    fn hash_raw_bytes(self, py: Python<'_>, fpath: &str, hash_size: u32) -> PyResult<Py<PyBytes>> {
+        let hash = self.hash(fpath, hash_size)?;
+        Ok(PyBytes::new(py, &hash.values).into())
+    }
+
+    // Hashes a base64 encoded image with the algorithm's default options.
+    fn hash_base64(self, data: &str, hash_size: u32) -> PyResult<ImageHash> {
+        self.hash_image(open_base64_image(data)?, hash_size, &HashOptions::default())
+    }
+}
+
+// Hashes an image sent as base64, such as a browser's `data:image/png;base64,...` URL.
+// The prefix is optional. Each algorithm uses its default options.
+#[pyfunction]
+fn ahash_base64(data_url: String, hash_size: u32) -> PyResult<ImageHash> {
+    Algorithm::Average.hash_base64(&data_url, hash_size)
+}
+
+#[pyfunction]
+fn dhash_base64(data_url: String, hash_size: u32) -> PyResult<ImageHash> {
+    Algorithm::Difference.hash_base64(&data_url, hash_size)
+}
+
+#[pyfunction]
+fn phash_base64(data_url: String, hash_size: u32) -> PyResult<ImageHash> {
+    Algorithm::Perceptual.hash_base64(&data_url, hash_size)
+}
+
+#[pyfunction]
+fn mhash_base64(data_url: String, hash_size: u32) -> PyResult<ImageHash> {
+    Algorithm::Median.hash_base64(&data_url, hash_size)
+}
+
+// Hashes an image and returns only the packed bytes of the hash, the same as its
+// `values`, for callers that store them directly. Each algorithm uses its default options.
+#[pyfunction]
+fn ahash_raw_bytes(py: Python<'_>, fpath: String, hash_size: u32) -> PyResult<Py<PyBytes>> {
+    Algorithm::Average.hash_raw_bytes(py, &fpath, hash_size)
+}
+
+#[pyfunction]
+fn dhash_raw_bytes(py: Python<'_>, fpath: String, hash_size: u32) -> PyResult<Py<PyBytes>> {
+    Algorithm::Difference.hash_raw_bytes(py, &fpath, hash_size)
+}
+
+#[pyfunction]
+fn phash_raw_bytes(py: Python<'_>, fpath: String, hash_size: u32) -> PyResult<Py<PyBytes>> {
+    Algorithm::Perceptual.hash_raw_bytes(py, &fpath, hash_size)
+}
+
+#[pyfunction]
+fn mhash_raw_bytes(py: Python<'_>, fpath: String, hash_size: u32) -> PyResult<Py<PyBytes>> {
+    Algorithm::Median.hash_raw_bytes(py, &fpath, hash_size)
+}
+
+// Hashes the frame shown at `timestamp_sec` seconds into a video file.
+// Requires the `video` feature.
+#[pyfunction]
+fn hash_video_frame(
+    fpath: String,
+    hash_size: u32,
+    algo: String,
+    timestamp_sec: f64,
+) -> PyResult<ImageHash> {
+    let algo = Algorithm::parse(&algo)?;
+    let frame = video::extract_frame(&fpath, timestamp_sec)?;
+    algo.hash_image(frame, hash_size, &HashOptions::default())
+}
+
+// Hashes a file with `algo`, reusing the hash stored in the cache file at `cache_path`
+// while the file's size and modification time are unchanged. Requires the `cache`
+// feature.
+#[pyfunction]
+fn hash_cached(
+    fpath: String,
+    hash_size: u32,
+    algo: String,
+    cache_path: String,
+) -> PyResult<ImageHash> {
+    let algo = Algorithm::parse(&algo)?;
+    cache::hash_cached(&fpath, hash_size, algo, &cache_path)
+}
+
+// `(entry name, hash)` of every image inside the zip or tar (optionally gzipped) archive
+// at `archive_path`, in archive order, without extracting it. Entries that are not images
+// are skipped, and images that cannot be decoded have no hash. The GIL is released while
+// hashing. Requires the `archive` feature.
+#[pyfunction]
+fn hash_archive(
+    py: Python<'_>,
+    archive_path: String,
+    hash_size: u32,
+    algo: String,
+) -> PyResult<Vec<(String, Option<ImageHash>)>> {
+    check_hash_size(hash_size)?;
+    let algo = Algorithm::parse(&algo)?;
+    py.allow_threads(|| archive::hash_archive(&archive_path, hash_size, algo))
+}
+
+// Hashes an image and also returns a PNG thumbnail of it fitting in `thumb_size` x
+// `thumb_size`, both from a single decode. The hash is the same `algo` would produce.
+#[pyfunction]
+fn hash_with_thumbnail(
+    fpath: String,
+    hash_size: u32,
+    thumb_size: u32,
+    algo: String,
+) -> PyResult<(ImageHash, Vec<u8>)> {
+    let algo = Algorithm::parse(&algo)?;
+    let img = open_image(&fpath)?;
+
+    let mut png: Vec<u8> = Vec::new();
+    if img
+        .thumbnail(thumb_size, thumb_size)
+        .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+        .is_err()
+    {
+        return Err(PyValueError::new_err("Cannot encode thumbnail."));
+    }
+
+    let hash = algo.hash_image(img, hash_size, &HashOptions::default())?;
+    Ok((hash, png))
+}
+
+// Hashes an image with `algo` at `levels` sizes doubling from `base_size`, coarsest first,
+// for coarse-to-fine search. The file is decoded once, and each level matches the hash
+// `algo` gives at that size directly.
+#[pyfunction]
+fn hash_pyramid(
+    fpath: String,
+    levels: u32,
+    base_size: u32,
+    algo: String,
+) -> PyResult<Vec<ImageHash>> {
+    let algo = Algorithm::parse(&algo)?;
+    let sizes: Option<Vec<u32>> = (0..levels)
+        .map(|level| base_size.checked_mul(1u32.checked_shl(level)?))
+        .collect();
+    let sizes = match sizes {
+        Some(sizes) if levels > 0 && base_size > 0 && sizes.iter().all(|s| *s <= 1 << 12) => sizes,
+        _ => {
+            return Err(PyValueError::new_err(
+                "Pyramid must have at least one level and sizes between 1 and 4096",
+            ))
+        }
+    };
+
+    let img = open_image(&fpath)?;
+    let opts = HashOptions::default();
+    sizes
+        .into_iter()
+        .map(|size| algo.hash_image(img.clone(), size, &opts))
+        .collect()
+}
+
+// Hashes every frame of an animated GIF, PNG or WebP with `algo` and its default options,
+// one hash per frame in display order. Any other image gives a single hash.
+#[pyfunction]
+fn hash_frames(fpath: String, hash_size: u32, algo: String) -> PyResult<Vec<ImageHash>> {
+    let algo = Algorithm::parse(&algo)?;
+    let opts = HashOptions::default();
+    open_frames(&fpath)?
+        .map(|frame| algo.hash_image(frame?, hash_size, &opts))
+        .collect()
+}
+
+// Distances between the hashes of consecutive frames of an animated GIF, PNG or WebP, one
+// per frame after the first. Scene cuts stand out as spikes. Frames are hashed with dhash
+// and its default options.
+#[pyfunction]
+fn frame_delta_hashes(fpath: String, hash_size: u32) -> PyResult<Vec<u32>> {
+    let mut deltas = Vec::new();
+    let mut previous: Option<ImageHash> = None;
+    for frame in open_frames(&fpath)? {
+        let hash = Algorithm::Difference.hash_image(frame?, hash_size, &HashOptions::default())?;
+        if let Some(previous) = &previous {
+            deltas.push(previous.hamming(&hash));
+        }
+        previous = Some(hash);
+    }
+    Ok(deltas)
+}
+
+// Hashes an image and a copy re-encoded as JPEG at quality 50 with every algorithm, and
+// returns the distance between the two per algorithm. Lower means the algorithm survives
+// compression better on this kind of content.
+#[pyfunction]
+fn robustness_report(fpath: String, hash_size: u32) -> PyResult<HashMap<String, u32>> {
+    let img = open_image(&fpath)?;
+    let rgb = flatten_alpha(img.clone(), DEFAULT_BACKGROUND).to_rgb8();
+    let recompressed = recompress_jpeg(&rgb, 50)?;
+
+    let opts = HashOptions::default();
+    let mut report = HashMap::new();
+    for algo in Algorithm::ALL {
+        let original = algo.hash_image(img.clone(), hash_size, &opts)?;
+        let compressed = algo.hash_image(recompressed.clone(), hash_size, &opts)?;
+        report.insert(algo.name().to_string(), original.hamming(&compressed));
+    }
+
+    Ok(report)
+}
+
+// Per bit of the `algo` hash of an image, the fraction of a fixed set of perturbations
+// that flip it: rotations by 1 degree either way, resampling at 98% and 102% of the size,
+// JPEG at quality 70 and brightness 5% up and down. Fragile positions can then be given
+// less weight when comparing.
+#[pyfunction]
+fn bit_error_profile(fpath: String, hash_size: u32, algo: String) -> PyResult<Vec<f64>> {
+    let algo = Algorithm::parse(&algo)?;
+    let rgb = flatten_alpha(open_image(&fpath)?, DEFAULT_BACKGROUND).to_rgb8();
+    let (width, height) = rgb.dimensions();
+    let resample = |scale: f64| {
+        let w = ((width as f64 * scale).round() as u32).max(1);
+        let h = ((height as f64 * scale).round() as u32).max(1);
+        imageops::resize(&rgb, w, h, FilterType::Triangle)
+    };
+
+    let variants = [
+        rotate_degrees(&rgb, 1.0),
+        rotate_degrees(&rgb, -1.0),
+        resample(0.98),
+        resample(1.02),
+        recompress_jpeg(&rgb, 70)?.to_rgb8(),
+        scale_brightness(&rgb, 1.05),
+        scale_brightness(&rgb, 0.95),
+    ];
+
+    let opts = HashOptions::default();
+    let original = algo.hash_image(DynamicImage::ImageRgb8(rgb.clone()), hash_size, &opts)?;
+    let mut flips = vec![0u32; original.bool_values.len()];
+    let count = variants.len() as f64;
+    for variant in variants {
+        let hash = algo.hash_image(DynamicImage::ImageRgb8(variant), hash_size, &opts)?;
+        for (count, (a, b)) in flips
+            .iter_mut()
+            .zip(original.bool_values.iter().zip(&hash.bool_values))
+        {
+            *count += (a != b) as u32;
+        }
+    }
+
+    Ok(flips
+        .into_iter()
+        .map(|flipped| flipped as f64 / count)
+        .collect())
+}
+
+// Re-encodes an image as JPEG at `quality` and decodes it back.
+fn recompress_jpeg(rgb: &RgbImage, quality: u8) -> PyResult<DynamicImage> {
+    let mut encoded: Vec<u8> = Vec::new();
+    let result = JpegEncoder::new_with_quality(&mut encoded, quality).encode(
+        rgb.as_raw(),
+        rgb.width(),
+        rgb.height(),
+        ColorType::Rgb8,
+    );
+    match result
+        .ok()
+        .and_then(|_| image::load_from_memory(&encoded).ok())
+    {
+        Some(im) => Ok(im),
+        None => Err(PyValueError::new_err("Cannot re-encode image.")),
+    }
+}
+
+// Rotates an image by `degrees` counterclockwise about its center with bilinear
+// sampling, keeping its size. Corners brought in from outside repeat the nearest edge.
+fn rotate_degrees(rgb: &RgbImage, degrees: f64) -> RgbImage {
+    let (width, height) = rgb.dimensions();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (cx, cy) = ((width as f64 - 1.0) / 2.0, (height as f64 - 1.0) / 2.0);
+    let (max_x, max_y) = (width as f64 - 1.0, height as f64 - 1.0);
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+        let sx = (cx + dx * cos - dy * sin).clamp(0.0, max_x);
+        let sy = (cy + dx * sin + dy * cos).clamp(0.0, max_y);
+        let (x0, y0) = (sx.floor() as u32, sy.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (fx, fy) = (sx - x0 as f64, sy - y0 as f64);
+
+        let mut px = [0u8; 3];
+        for (c, value) in px.iter_mut().enumerate() {
+            let at = |x: u32, y: u32| rgb.get_pixel(x, y).0[c] as f64;
+            let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+            let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+            *value = (top * (1.0 - fy) + bottom * fy).round() as u8;
+        }
+        Rgb(px)
+    })
+}
+
+// Rotates content tilted by a few degrees back to the axes, see `skew`.
+fn deskew(img: DynamicImage) -> DynamicImage {
+    let angle = skew::estimate_skew(&img);
+    if angle.abs() < 0.1 {
+        return img;
+    }
+    DynamicImage::ImageRgb8(rotate_degrees(&img.to_rgb8(), angle))
+}
+
+// Multiplies every channel by `factor`, saturating at white.
+fn scale_brightness(rgb: &RgbImage, factor: f64) -> RgbImage {
+    let mut scaled = rgb.clone();
+    for px in scaled.pixels_mut() {
+        for value in px.0.iter_mut() {
+            *value = (*value as f64 * factor).round().min(255.0) as u8;
+        }
+    }
+    scaled
+}
+
+// Hashes a small built-in test image with every algorithm at `hash_size` 8 and returns
+// one `algorithm:hex` line per algorithm. The image goes through a PNG encode and decode
+// first, so the digests change whenever the `image` crate's codecs, resizing or luma
+// conversion do. Assert them in CI to catch hashes drifting after an upgrade.
+#[pyfunction]
+fn self_check() -> PyResult<String> {
+    // Diagonal gradients with a bright disc off center, enough structure for every bit
+    // of every algorithm to depend on the image.
+    let img = RgbImage::from_fn(96, 64, |x, y| {
+        let in_disc = (x as i32 - 60).pow(2) + (y as i32 - 24).pow(2) < 15 * 15;
+        if in_disc {
+            Rgb([240, 230, 200])
+        } else {
+            Rgb([(x * 2 + y) as u8, (y * 3) as u8, (255 - x * 2) as u8])
+        }
+    });
+
+    let mut png = Cursor::new(Vec::new());
+    let decoded = DynamicImage::ImageRgb8(img)
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .ok()
+        .and_then(|()| image::load_from_memory(png.get_ref()).ok());
+    let decoded = match decoded {
+        Some(decoded) => decoded,
+        None => return Err(PyValueError::new_err("Cannot round trip the test image.")),
+    };
+
+    let opts = HashOptions::default();
+    let mut lines = Vec::new();
+    for algo in Algorithm::ALL {
+        let hash = algo.hash_image(decoded.clone(), 8, &opts)?;
+        lines.push(format!("{}:{}", algo.name(), hash.to_hex()));
+    }
+    Ok(lines.join("\n"))
+}
+
+// Probability that two random hashes of `hash_bits` bits are within `threshold`
+// Hamming distance, i.e. the tail P(X <= threshold) of X ~ Binomial(hash_bits, 0.5).
+// This estimates the false positive rate of a distance threshold.
+#[pyfunction]
+fn collision_probability(hash_bits: u32, threshold: u32) -> f64 {
+    if threshold >= hash_bits {
+        return 1.0;
+    }
+
+    // Work in log space, 2^-n underflows for the larger hash sizes.
+    let n = hash_bits as f64;
+    let mut log_choose = 0.0f64;
+    let mut probability = 0.0f64;
+    for k in 0..=threshold {
+        if k > 0 {
+            log_choose += (n - k as f64 + 1.0).ln() - (k as f64).ln();
+        }
+        probability += (log_choose - n * std::f64::consts::LN_2).exp();
+    }
+    probability.min(1.0)
+}
+
+// Counts, per bit position, how many of the hashes have that bit set.
+#[pyfunction]
+fn bit_frequency(hashes: Vec<PyRef<ImageHash>>) -> PyResult<Vec<u32>> {
+    let first = match hashes.first() {
+        Some(hash) => hash,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut counts = vec![0u32; first.bool_values.len()];
+    for hash in &hashes {
+        first.check_shape(hash)?;
+
+        for (count, bit) in counts.iter_mut().zip(&hash.bool_values) {
+            *count += *bit as u32;
+        }
+    }
+
+    Ok(counts)
+}
+
+// How well each bit position separates duplicates from non-duplicates, learned from
+// labeled `(a, b, is_duplicate)` pairs: the mutual information, in bits, between the
+// position agreeing in a pair and the pair being a duplicate. A position that agrees no
+// more often among duplicates than among the others tells nothing and gets 0, so the
+// result can be passed to `ImageHash.weighted_distance` as is.
+#[pyfunction]
+fn learn_bit_weights(pairs: Vec<(PyRef<ImageHash>, PyRef<ImageHash>, bool)>) -> PyResult<Vec<f64>> {
+    let first = match pairs.first() {
+        Some((hash, _, _)) => hash,
+        None => return Err(PyValueError::new_err("No labeled pairs")),
+    };
+    let bits = first.bool_values.len();
+
+    // counts[c][duplicate][agree]: pairs with that label and agreement at position `c`.
+    let mut counts = vec![[[0u64; 2]; 2]; bits];
+    for (a, b, duplicate) in &pairs {
+        first.check_shape(a)?;
+        first.check_shape(b)?;
+        for (count, (x, y)) in counts
+            .iter_mut()
+            .zip(a.bool_values.iter().zip(&b.bool_values))
+        {
+            count[*duplicate as usize][(x == y) as usize] += 1;
+        }
+    }
+
+    let total = pairs.len() as f64;
+    Ok(counts
+        .iter()
+        .map(|count| {
+            let labeled = |duplicate: usize| (count[duplicate][0] + count[duplicate][1]) as f64;
+            let agreeing = |agree: usize| (count[0][agree] + count[1][agree]) as f64;
+            let agreement_rate = |duplicate: usize| match labeled(duplicate) {
+                n if n > 0.0 => count[duplicate][1] as f64 / n,
+                _ => 0.0,
+            };
+            if agreement_rate(1) <= agreement_rate(0) {
+                return 0.0;
+            }
+
+            let mut information = 0.0;
+            for (duplicate, by_agreement) in count.iter().enumerate() {
+                for (agree, joint) in by_agreement.iter().enumerate() {
+                    let joint = *joint as f64;
+                    if joint > 0.0 {
+                        information += joint / total
+                            * (joint * total / (labeled(duplicate) * agreeing(agree))).log2();
+                    }
+                }
+            }
+            information.max(0.0)
+        })
+        .collect())
+}
+
+// Per-bit majority vote over the hashes, a single representative of a group of near
+// duplicates. A bit is set when more than half of the hashes have it, a tie leaves it
+// unset.
+#[pyfunction]
+fn centroid(hashes: Vec<PyRef<ImageHash>>) -> PyResult<ImageHash> {
+    let (width, height) = match hashes.first() {
+        Some(hash) => (hash.width, hash.height),
+        None => return Err(PyValueError::new_err("No hashes given")),
+    };
+
+    let total = hashes.len() as u32;
+    let majority = bit_frequency(hashes)?
+        .into_iter()
+        .map(|count| 2 * count > total)
+        .collect();
+    ImageHash::new_rect(majority, width, height)
+}
+
+// Smallest total distance over one-to-one pairings of the hashes in `a` with those in `b`,
+// such as the segments of two crop-resistant hashes. When the lists differ in length,
+// the extra hashes of the longer one stay unmatched and cost nothing.
+#[pyfunction]
+fn assignment_distance(a: Vec<PyRef<ImageHash>>, b: Vec<PyRef<ImageHash>>) -> PyResult<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Err(PyValueError::new_err("No hashes given"));
+    }
+
+    // The solver needs at most as many rows as columns.
+    let (rows, cols) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    let mut cost = Vec::with_capacity(rows.len());
+    for row in rows.iter() {
+        let mut line = Vec::with_capacity(cols.len());
+        for col in cols.iter() {
+            line.push(row.distance(col)? as i64);
+        }
+        cost.push(line);
+    }
+
+    Ok(min_cost_assignment(&cost) as u32)
+}
+
+// Hungarian algorithm: the minimum total cost of assigning every row of `cost` to a
+// distinct column, in O(rows^2 * cols). Requires no more rows than columns.
+fn min_cost_assignment(cost: &[Vec<i64>]) -> i64 {
+    let (n, m) = (cost.len(), cost[0].len());
+    // Potentials and matching are 1-indexed, with column 0 a virtual starting point.
+    let mut u = vec![0i64; n + 1];
+    let mut v = vec![0i64; m + 1];
+    let mut row_of = vec![0usize; m + 1];
+    let mut way = vec![0usize; m + 1];
+
+    for i in 1..=n {
+        row_of[0] = i;
+        let mut j0 = 0;
+        let mut min_slack = vec![i64::MAX; m + 1];
+        let mut used = vec![false; m + 1];
+
+        // Grow an alternating tree until it reaches a free column.
+        loop {
+            used[j0] = true;
+            let i0 = row_of[j0];
+            let mut delta = i64::MAX;
+            let mut j1 = 0;
+            for j in 1..=m {
+                if used[j] {
+                    continue;
+                }
+                let slack = cost[i0 - 1][j - 1] - u[i0] - v[j];
+                if slack < min_slack[j] {
+                    min_slack[j] = slack;
+                    way[j] = j0;
+                }
+                if min_slack[j] < delta {
+                    delta = min_slack[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=m {
+                if used[j] {
+                    u[row_of[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_slack[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if row_of[j0] == 0 {
+                break;
+            }
+        }
+
+        // Flip the augmenting path.
+        while j0 != 0 {
+            let j1 = way[j0];
+            row_of[j0] = row_of[j1];
+            j0 = j1;
+        }
+    }
+
+    (1..=m)
+        .filter(|j| row_of[*j] != 0)
+        .map(|j| cost[row_of[j] - 1][j - 1])
+        .sum()
+}
+
+// Finds the root of `i` in a union-find forest, compressing the path on the way.
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+
+    let mut current = i;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+// Counting semaphore, used to bound how many images are held decoded at once whatever
+// the number of threads.
+struct Semaphore {
+    permits: Mutex<usize>,
+    released: Condvar,
+}
+
+// Permit held until dropped.
+struct Permit<'a>(&'a Semaphore);
+
+impl Semaphore {
+    fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            permits: Mutex::new(permits),
+            released: Condvar::new(),
+        }
+    }
+
+    // Blocks until a permit is free.
+    fn acquire(&self) -> Permit<'_> {
+        let mut permits = self.permits.lock().unwrap();
+        while *permits == 0 {
+            permits = self.released.wait(permits).unwrap();
+        }
+        *permits -= 1;
+        Permit(self)
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.0.permits.lock().unwrap() += 1;
+        self.0.released.notify_one();
+    }
+}
+
+// Hashes every path in parallel and groups the ones within `threshold` distance of
+// each other. Files that cannot be hashed are ignored, and groups with a single file
+// are omitted. With `max_inflight`, at most that many images are decoded at a time,
+// which bounds memory on machines with many cores; the groups are the same.
+#[pyfunction]
+fn find_duplicates(
+    py: Python<'_>,
+    paths: Vec<String>,
+    hash_size: u32,
+    algo: String,
+    threshold: u32,
+    max_inflight: Option<usize>,
+) -> PyResult<Vec<Vec<String>>> {
+    check_hash_size(hash_size)?;
+    let algo = Algorithm::parse(&algo)?;
+    if max_inflight == Some(0) {
+        return Err(PyValueError::new_err("max_inflight must be at least 1"));
+    }
+    let inflight = max_inflight.map(Semaphore::new);
+
+    let groups = py.allow_threads(|| {
+        let hashes: Vec<Option<ImageHash>> = paths
+            .par_iter()
+            .map(|path| {
+                let _permit = inflight.as_ref().map(Semaphore::acquire);
+                algo.hash(path, hash_size).ok()
+            })
+            .collect();
+
+        let mut parents: Vec<usize> = (0..paths.len()).collect();
+        for i in 0..hashes.len() {
+            let base = match &hashes[i] {
+                Some(hash) => hash,
+                None => continue,
+            };
+
+            for (j, target) in hashes.iter().enumerate().skip(i + 1) {
+                let target = match target {
+                    Some(hash) => hash,
+                    None => continue,
+                };
+
+                if let Ok(distance) = base.distance(target) {
+                    if distance <= threshold {
+                        let (a, b) = (find_root(&mut parents, i), find_root(&mut parents, j));
+                        parents[b.max(a)] = a.min(b);
+                    }
+                }
+            }
+        }
+
+        let mut groups: Vec<Vec<String>> = vec![Vec::new(); paths.len()];
+        for (i, path) in paths.iter().enumerate() {
+            groups[find_root(&mut parents, i)].push(path.clone());
+        }
+        groups
+    });
+
+    Ok(groups.into_iter().filter(|group| group.len() > 1).collect())
+}
+
+// Mean normalized distance over every pair of images in the directory `dir`, from 0.0
+// when they all hash alike to about 0.5 for unrelated images: low values point to a
+// collection full of near-duplicates. Files that cannot be hashed are ignored, and
+// subdirectories are not visited. The GIL is released while hashing and comparing.
+#[pyfunction]
+fn diversity_report(py: Python<'_>, dir: String, hash_size: u32, algo: String) -> PyResult<f64> {
+    check_hash_size(hash_size)?;
+    let algo = Algorithm::parse(&algo)?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_e) => return Err(PyValueError::new_err("Cannot read directory.")),
+    };
+    let paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+
+    let diversity = py.allow_threads(|| {
+        let hashes: Vec<ImageHash> = paths
+            .par_iter()
+            .filter_map(|path| algo.hash(path, hash_size).ok())
+            .collect();
+        if hashes.len() < 2 {
+            return None;
+        }
+
+        let total: u64 = (0..hashes.len())
+            .into_par_iter()
+            .map(|i| {
+                hashes[i + 1..]
+                    .iter()
+                    .map(|other| hashes[i].hamming(other) as u64)
+                    .sum::<u64>()
+            })
+            .sum();
+        let pairs = (hashes.len() * (hashes.len() - 1) / 2) as f64;
+        let bits = hashes[0].bool_values.len() as f64;
+        Some(total as f64 / pairs / bits)
+    });
+
+    match diversity {
+        Some(diversity) => Ok(diversity),
+        None => Err(PyValueError::new_err(
+            "Directory has fewer than two images to compare",
+        )),
+    }
+}
+
+// Indices of the `incoming` hashes farther than `threshold` from every `existing` one,
+// that is the genuinely new items of an incremental import. The GIL is released while
+// comparing.
+#[pyfunction]
+fn merge_galleries(
+    py: Python<'_>,
+    existing: Vec<PyRef<ImageHash>>,
+    incoming: Vec<PyRef<ImageHash>>,
+    threshold: u32,
+) -> PyResult<Vec<usize>> {
+    if let Some(first) = existing.first() {
+        for hash in existing.iter().chain(&incoming) {
+            first.check_shape(hash)?;
+        }
+    }
+
+    let existing: Vec<&ImageHash> = existing.iter().map(|hash| &**hash).collect();
+    let incoming: Vec<&ImageHash> = incoming.iter().map(|hash| &**hash).collect();
+    Ok(py.allow_threads(|| {
+        incoming
+            .iter()
+            .enumerate()
+            .filter(|(_, hash)| existing.iter().all(|known| known.hamming(hash) > threshold))
+            .map(|(i, _)| i)
+            .collect()
+    }))
+}
+
+// Splits a wide image such as a panorama into `strips` side by side segments of equal
+// width and perceptual hashes each, left to right. Squashing the whole panorama into a
+// single square hash would lose most of its detail.
+#[pyfunction]
+fn strip_hash(fpath: String, strips: u32, hash_size: u32) -> PyResult<Vec<ImageHash>> {
+    let img = open_image(&fpath)?;
+    let (width, height) = img.dimensions();
+    if strips == 0 || strips > width {
+        return Err(PyValueError::new_err(
+            "Strips must be between 1 and the image width",
+        ));
+    }
+
+    let opts = HashOptions::default();
+    (0..strips)
+        .map(|i| {
+            let left = i * width / strips;
+            let right = (i + 1) * width / strips;
+            let strip = img.crop_imm(left, 0, right - left, height);
+            perceptual_hash(strip, hash_size, &opts)
+        })
+        .collect()
+}
+
+// Distance between two `strip_hash` results, aligning the shorter sequence of strips
+// with the longer at the offset where they match best. A panorama cropped on strip
+// boundaries and hashed with proportionally fewer strips is at distance 0.
+#[pyfunction]
+fn strip_distance(a: Vec<PyRef<ImageHash>>, b: Vec<PyRef<ImageHash>>) -> PyResult<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Err(PyValueError::new_err("No hashes given"));
+    }
+
+    let (short, long) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    let mut best: Option<u32> = None;
+    for offset in 0..=long.len() - short.len() {
+        let mut total = 0;
+        for (s, l) in short.iter().zip(&long[offset..]) {
+            total += s.distance(l)?;
+        }
+        best = Some(best.map_or(total, |b| b.min(total)));
+    }
+
+    Ok(best.unwrap_or(0))
+}
+
+// Perceptual hash distance between two images after undoing a translation between them.
+// The shift is estimated by phase correlation, and both images are cropped to the area
+// they have in common before being hashed, so a shifted copy stays close to its
+// original even when the shift is large enough to change every phash bit.
+#[pyfunction]
+fn aligned_phash(
+    path_a: String,
+    path_b: String,
+    hash_size: u32,
+    highfreq_factor: u32,
+) -> PyResult<u32> {
+    let a = open_image(&path_a)?;
+    let b = open_image(&path_b)?;
+    let shift = align::estimate_shift(&a, &b);
+    let (a, b) = align::crop_overlap(a, b, shift);
+
+    let opts = HashOptions {
+        highfreq_factor,
+        ..Default::default()
+    };
+    let a = perceptual_hash(a, hash_size, &opts)?;
+    let b = perceptual_hash(b, hash_size, &opts)?;
+    Ok(a.hamming(&b))
+}
+
+// Whether two files have the same content, only reading them when their sizes match.
+fn same_bytes(path_a: &str, path_b: &str) -> PyResult<bool> {
+    let same_len = match (fs::metadata(path_a), fs::metadata(path_b)) {
+        (Ok(a), Ok(b)) => a.len() == b.len(),
+        _ => return Err(PyValueError::new_err("Cannot read file.")),
+    };
+    if !same_len {
+        return Ok(false);
+    }
+
+    match (fs::read(path_a), fs::read(path_b)) {
+        (Ok(a), Ok(b)) => Ok(a == b),
+        _ => Err(PyValueError::new_err("Cannot read file.")),
+    }
+}
+
+// Distance between the `algo` hashes of two files, each decoded once. Files with the
+// same bytes are at distance 0 without being decoded at all.
+#[pyfunction]
+fn compare_files(path_a: String, path_b: String, hash_size: u32, algo: String) -> PyResult<u32> {
+    let algo = Algorithm::parse(&algo)?;
+    if same_bytes(&path_a, &path_b)? {
+        return Ok(0);
+    }
+
+    let a = algo.hash(&path_a, hash_size)?;
+    let b = algo.hash(&path_b, hash_size)?;
+    Ok(a.hamming(&b))
+}
+
+// Distance between the `algo` hashes of two encoded images held in memory, such as two
+// uploads, without writing them to disk.
+#[pyfunction]
+fn compare_bytes(a: &[u8], b: &[u8], hash_size: u32, algo: String) -> PyResult<u32> {
+    let algo = Algorithm::parse(&algo)?;
+    let decode = |data: &[u8], name: &str| match image::load_from_memory(data) {
+        Ok(im) => Ok(im),
+        Err(_e) => Err(PyValueError::new_err(format!(
+            "Cannot open image {}.",
+            name
+        ))),
+    };
+    let img_a = decode(a, "a")?;
+    let img_b = decode(b, "b")?;
+
+    let opts = HashOptions::default();
+    let a = algo.hash_image(img_a, hash_size, &opts)?;
+    let b = algo.hash_image(img_b, hash_size, &opts)?;
+    Ok(a.hamming(&b))
+}
+
+// Classifies two files as "identical" (same bytes), "near-duplicate" (perceptual hashes
+// within `threshold`) or "different". Identical files are recognized without decoding
+// them, and the bytes are only compared when the file sizes match.
+#[pyfunction]
+fn classify_pair(
+    path_a: String,
+    path_b: String,
+    hash_size: u32,
+    threshold: u32,
+) -> PyResult<String> {
+    if same_bytes(&path_a, &path_b)? {
+        return Ok("identical".to_string());
+    }
+
+    let a = Algorithm::Perceptual.hash(&path_a, hash_size)?;
+    let b = Algorithm::Perceptual.hash(&path_b, hash_size)?;
+    let class = if a.hamming(&b) <= threshold {
+        "near-duplicate"
+    } else {
+        "different"
+    };
+    Ok(class.to_string())
+}
+
+// `algo` hash of a file along with the aspect ratio (width over height) of the image,
+// which the hash alone does not keep since every image is resized to a square grid.
+#[pyfunction]
+fn signature(fpath: String, hash_size: u32, algo: String) -> PyResult<(ImageHash, f64)> {
+    let algo = Algorithm::parse(&algo)?;
+    let img = open_image(&fpath)?;
+    let aspect = img.width() as f64 / img.height() as f64;
+    let hash = algo.hash_image(img, hash_size, &HashOptions::default())?;
+    Ok((hash, aspect))
+}
+
+// Distance between two signatures from `signature`: the Hamming distance between the
+// hashes plus `aspect_weight` (1.0 by default) times the hash length for every doubling
+// of one aspect ratio over the other. Copies stretched or cropped to a different shape
+// then score far apart even when their square resized hashes match.
+#[pyfunction]
+fn signature_distance(
+    hash_a: &ImageHash,
+    aspect_a: f64,
+    hash_b: &ImageHash,
+    aspect_b: f64,
+    aspect_weight: Option<f64>,
+) -> PyResult<f64> {
+    hash_a.check_shape(hash_b)?;
+    if !(aspect_a > 0.0 && aspect_b > 0.0) {
+        return Err(PyValueError::new_err("Aspect ratios must be positive"));
+    }
+
+    let bits = hash_a.bool_values.len() as f64;
+    let penalty = aspect_weight.unwrap_or(1.0) * bits * (aspect_a / aspect_b).log2().abs();
+    Ok(hash_a.hamming(hash_b) as f64 + penalty)
+}
+
+// Weighted mean of the normalized ahash, dhash and phash distances between two files,
+// from 0.0 for matching hashes to 1.0 for opposite ones. A pair only scores low when the
+// algorithms agree, which makes the score much less prone to false positives than any
+// single distance. `weights` default to equal weights, in ahash, dhash, phash order.
+#[pyfunction]
+fn ensemble_distance(
+    path_a: String,
+    path_b: String,
+    hash_size: u32,
+    weights: Option<(f64, f64, f64)>,
+) -> PyResult<f64> {
+    let (wa, wd, wp) = weights.unwrap_or((1.0, 1.0, 1.0));
+    let total_weight = wa + wd + wp;
+    if wa < 0.0 || wd < 0.0 || wp < 0.0 || total_weight <= 0.0 {
+        return Err(PyValueError::new_err(
+            "Weights must not be negative and must not all be zero",
+        ));
+    }
+
+    let [(_, da), (_, dd), (_, dp)] = ensemble_scores(&path_a, &path_b, hash_size)?;
+    Ok((wa * da + wd * dd + wp * dp) / total_weight)
+}
+
+// The normalized ahash, dhash and phash distances between two files, and under "ensemble"
+// their mean as `ensemble_distance` computes it with equal weights. Each file is decoded
+// once.
+#[pyfunction]
+fn compare_detailed(
+    path_a: String,
+    path_b: String,
+    hash_size: u32,
+) -> PyResult<HashMap<String, f64>> {
+    let scores = ensemble_scores(&path_a, &path_b, hash_size)?;
+    let mut detailed: HashMap<String, f64> = scores
+        .iter()
+        .map(|(algo, score)| (algo.name().to_string(), *score))
+        .collect();
+    let ensemble = scores.iter().map(|(_, score)| score).sum::<f64>() / scores.len() as f64;
+    detailed.insert("ensemble".to_string(), ensemble);
+    Ok(detailed)
+}
+
+// Normalized distance between two files under each algorithm of the ensemble.
+fn ensemble_scores(path_a: &str, path_b: &str, hash_size: u32) -> PyResult<[(Algorithm, f64); 3]> {
+    let (img_a, img_b) = (open_image(path_a)?, open_image(path_b)?);
+    let opts = HashOptions::default();
+    let mut scores = [
+        (Algorithm::Average, 0.0),
+        (Algorithm::Difference, 0.0),
+        (Algorithm::Perceptual, 0.0),
+    ];
+    for (algo, score) in scores.iter_mut() {
+        let a = algo.hash_image(img_a.clone(), hash_size, &opts)?;
+        let b = algo.hash_image(img_b.clone(), hash_size, &opts)?;
+        *score = a.hamming(&b) as f64 / a.bool_values.len().max(1) as f64;
+    }
+    Ok(scores)
+}
+
+// Compares two hashes, returning the distance, normalized distance and similarity at once.
+#[pyfunction]
+fn compare(a: &ImageHash, b: &ImageHash) -> PyResult<Comparison> {
+    if !a.same_shape(b) {
+        return Ok(Comparison {
+            distance: None,
+            normalized_distance: None,
+            similarity: None,
+            are_comparable: false,
+        });
+    }
+
+    let distance = a.hamming(b);
+    let normalized = distance as f64 / a.bool_values.len().max(1) as f64;
+    Ok(Comparison {
+        distance: Some(distance),
+        normalized_distance: Some(normalized),
+        similarity: Some(1.0 - normalized),
+        are_comparable: true,
+    })
+}
+
+// Renders two hashes side by side as PNG, with the cells that differ painted red.
+// Set bits are white and unset bits are black. Each cell is `scale` pixels wide and the
+// grids are separated by one blank cell, so the output is
+// `(2 * width + 1) * scale` by `height * scale` pixels.
+#[pyfunction]
+fn render_diff(a: &ImageHash, b: &ImageHash, out_path: String, scale: u32) -> PyResult<()> {
+    a.check_shape(b)?;
+    if scale == 0 {
+        return Err(PyValueError::new_err("Scale must be positive."));
+    }
+
+    let (grid_width, grid_height) = (a.width as u32, a.height as u32);
+    let width = (2 * grid_width + 1) * scale;
+    let height = grid_height * scale;
+    let mut canvas = RgbImage::from_pixel(width, height, Rgb([128, 128, 128]));
+
+    for (grid, hash) in [a, b].iter().enumerate() {
+        let x_offset = grid as u32 * (grid_width + 1) * scale;
+        for c in 0..hash.bool_values.len() {
+            let color = if a.bool_values[c] != b.bool_values[c] {
+                Rgb([255, 0, 0])
+            } else if hash.bool_values[c] {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            };
+
+            let cell_x = x_offset + (c as u32 % grid_width) * scale;
+            let cell_y = (c as u32 / grid_width) * scale;
+            for y in cell_y..cell_y + scale {
+                for x in cell_x..cell_x + scale {
+                    canvas.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    match canvas.save_with_format(out_path, image::ImageFormat::Png) {
+        Ok(()) => Ok(()),
+        Err(_e) => Err(PyValueError::new_err("Cannot write image.")),
+    }
+}
+
+// Cursor over a byte buffer that fails cleanly on truncated input.
+struct ByteReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> PyResult<&'a [u8]> {
+        match self.buf.get(self.pos..self.pos + len) {
+            Some(chunk) => {
+                self.pos += len;
+                Ok(chunk)
+            }
+            None => Err(PyValueError::new_err("Corrupt hashes file.")),
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    fn take_u32(&mut self) -> PyResult<usize> {
+        let bytes: [u8; 4] = self.take(4)?.try_into().unwrap();
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+}
+
+// Writes hashes to a compact binary file.
+//
+// Layout (integers are little endian): the `DIFH` magic, a version byte and the number
+// of hashes as u32, then for each hash the width and height of its bit grid as u32, the
+// name of its algorithm as a length byte and that many bytes, empty when unknown, and its
+// packed byte length as u32 followed by the packed bytes.
+#[pyfunction]
+fn save_hashes(hashes: Vec<PyRef<ImageHash>>, path: String) -> PyResult<()> {
+    let mut buf: Vec<u8> = Vec::new();
+    buf.extend_from_slice(HASHES_MAGIC);
+    buf.push(HASHES_VERSION);
+    buf.extend_from_slice(&(hashes.len() as u32).to_le_bytes());
+
+    for hash in &hashes {
+        buf.extend_from_slice(&(hash.width as u32).to_le_bytes());
+        buf.extend_from_slice(&(hash.height as u32).to_le_bytes());
+        let algorithm = hash.algorithm.map(Algorithm::name).unwrap_or("");
+        buf.push(algorithm.len() as u8);
+        buf.extend_from_slice(algorithm.as_bytes());
+        buf.extend_from_slice(&(hash.values.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&hash.values);
+    }
+
+    match fs::write(path, buf) {
+        Ok(()) => Ok(()),
+        Err(_e) => Err(PyValueError::new_err("Cannot write hashes.")),
+    }
+}
+
+// Reads hashes written by `save_hashes`. Files of version 1, whose records hold a single
+// `hash_size` for square hashes and no algorithm, can still be read.
+#[pyfunction]
+fn load_hashes(path: String) -> PyResult<Vec<ImageHash>> {
+    let buf = match fs::read(path) {
+        Ok(buf) => buf,
+        Err(_e) => return Err(PyValueError::new_err("Cannot read hashes.")),
+    };
+
+    let mut reader = ByteReader { buf: &buf, pos: 0 };
+    if reader.take(4)? != HASHES_MAGIC {
+        return Err(PyValueError::new_err("Not a hashes file."));
+    }
+    let version = reader.take(1)?[0];
+    if version != 1 && version != HASHES_VERSION {
+        return Err(PyValueError::new_err("Unsupported hashes file version."));
+    }
+    let header_len = if version == 1 { 8 } else { 13 };
+
+    // Every record takes at least its fixed fields, so a count the rest of the file cannot
+    // hold is corrupt rather than a reason to reserve that much memory.
+    let count = reader.take_u32()?;
+    if count > reader.remaining() / header_len {
+        return Err(PyValueError::new_err("Corrupt hashes file."));
+    }
+    let mut hashes = Vec::with_capacity(count);
+    for _ in 0..count {
+        let width = reader.take_u32()?;
+        let (height, algorithm) = if version == 1 {
+            (width, None)
+        } else {
+            let height = reader.take_u32()?;
+            let name_len = reader.take(1)?[0] as usize;
+            let algorithm = match std::str::from_utf8(reader.take(name_len)?) {
+                Ok("") => None,
+                Ok(name) => Some(Algorithm::parse(name)?),
+                Err(_e) => return Err(PyValueError::new_err("Corrupt hashes file.")),
+            };
+            (height, algorithm)
+        };
+        let len = reader.take_u32()?;
+        let bits = match width.checked_mul(height) {
+            Some(bits) if len == bits.div_ceil(8) => bits,
+            _ => return Err(PyValueError::new_err("Corrupt hashes file.")),
+        };
+        let values = reader.take(len)?;
+
+        let mut hash = ImageHash::new_rect(unpack_bits(values, bits), width, height)?;
+        hash.algorithm = algorithm;
+        hashes.push(hash);
+    }
+
+    Ok(hashes)
+}
+
+// Reads the rows written by `to_csv_row` back into `(path, hash)` pairs. A leading
+// `path,algorithm,hash_size,hex` header row is skipped.
+#[pyfunction]
+fn load_csv(file: String) -> PyResult<Vec<(String, ImageHash)>> {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(_e) => return Err(PyValueError::new_err("Cannot read CSV.")),
+    };
+
+    let mut rows = parse_csv(&text).into_iter().peekable();
+    if rows.peek().is_some_and(|row| row == &CSV_HEADER) {
+        rows.next();
+    }
+
+    rows.map(|row| {
+        let [path, algorithm, hash_size, hex] = match <[String; 4]>::try_from(row) {
+            Ok(fields) => fields,
+            Err(_row) => return Err(PyValueError::new_err("CSV rows must have 4 fields")),
+        };
+        let (width, height) = match parse_grid(&hash_size) {
+            Some(grid) => grid,
+            None => return Err(PyValueError::new_err("Invalid hash size in CSV.")),
+        };
+        let mut hash = match ImageHash::from_hex(&hex, width, height) {
+            Some(hash) => hash,
+            None => return Err(PyValueError::new_err("Invalid hex hash in CSV.")),
+        };
+        if !algorithm.is_empty() {
+            hash = hash.with_algorithm(Algorithm::parse(&algorithm)?);
+        }
+        Ok((path, hash))
+    })
+    .collect()
+}
+
+const CSV_HEADER: [&str; 4] = ["path", "algorithm", "hash_size", "hex"];
+
+// `(width, height)` of a `widthxheight` hash size field, or of a square one given by its
+// side alone as older rows have it.
+fn parse_grid(field: &str) -> Option<(usize, usize)> {
+    match field.split_once('x') {
+        Some((width, height)) => Some((width.parse().ok()?, height.parse().ok()?)),
+        None => field.parse().ok().map(|side| (side, side)),
+    }
+}
+
+// Quotes a CSV field when it holds a separator, quote or line break, doubling its quotes.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Splits CSV text into rows of fields, undoing the quoting of `csv_field`. Blank lines
+// are skipped.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match (ch, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                let done = std::mem::take(&mut row);
+                if done.len() > 1 || !done[0].is_empty() {
+                    rows.push(done);
+                }
+            }
+            _ => field.push(ch),
+        }
+    }
+    if !row.is_empty() || !field.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
+
+// Bytes of a hex string with two digits per byte, or `None` if it is not valid hex.
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// A Python module implemented in Rust.
+#[pymodule]
+fn dif(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<ImageHash>()?;
+    m.add_class::<Comparison>()?;
+    m.add_class::<Timings>()?;
+    m.add_class::<tiled::TiledHasher>()?;
+    m.add_class::<hasher::HashConfig>()?;
+    m.add_class::<hasher::Hasher>()?;
+    m.add_function(wrap_pyfunction!(ahash, m)?)?;
+    m.add_function(wrap_pyfunction!(ahash_async, m)?)?;
+    m.add_function(wrap_pyfunction!(ahash_custom, m)?)?;
+    m.add_function(wrap_pyfunction!(ahash_from_grayscale_buffer, m)?)?;
+    m.add_function(wrap_pyfunction!(ahash_from_matrix, m)?)?;
+    m.add_function(wrap_pyfunction!(ahash_from_ndarray, m)?)?;
+    m.add_function(wrap_pyfunction!(dhash, m)?)?;
+    m.add_function(wrap_pyfunction!(dhash_pair, m)?)?;
+    m.add_function(wrap_pyfunction!(grayscale_array, m)?)?;
+    m.add_function(wrap_pyfunction!(phash, m)?)?;
+    m.add_function(wrap_pyfunction!(phash_debug, m)?)?;
+    m.add_function(wrap_pyfunction!(phash_spectral, m)?)?;
+    m.add_function(wrap_pyfunction!(mhash, m)?)?;
+    m.add_function(wrap_pyfunction!(text_robust_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(quad_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(quad_distance, m)?)?;
+    m.add_function(wrap_pyfunction!(alpha_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(thumbhash, m)?)?;
+    m.add_function(wrap_pyfunction!(ahash_base64, m)?)?;
+    m.add_function(wrap_pyfunction!(dhash_base64, m)?)?;
+    m.add_function(wrap_pyfunction!(phash_base64, m)?)?;
+    m.add_function(wrap_pyfunction!(mhash_base64, m)?)?;
+    m.add_function(wrap_pyfunction!(ahash_raw_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(dhash_raw_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(phash_raw_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(mhash_raw_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_video_frame, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_with_thumbnail, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_pyramid, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_frames, m)?)?;
+    m.add_function(wrap_pyfunction!(frame_delta_hashes, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_cached, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_archive, m)?)?;
+    m.add_function(wrap_pyfunction!(find_duplicates, m)?)?;
+    m.add_function(wrap_pyfunction!(diversity_report, m)?)?;
+    m.add_function(wrap_pyfunction!(assignment_distance, m)?)?;
+    m.add_function(wrap_pyfunction!(merge_galleries, m)?)?;
+    m.add_function(wrap_pyfunction!(robustness_report, m)?)?;
+    m.add_function(wrap_pyfunction!(bit_error_profile, m)?)?;
+    m.add_function(wrap_pyfunction!(self_check, m)?)?;
+    m.add_function(wrap_pyfunction!(collision_probability, m)?)?;
+    m.add_function(wrap_pyfunction!(bit_frequency, m)?)?;
+    m.add_function(wrap_pyfunction!(learn_bit_weights, m)?)?;
+    m.add_function(wrap_pyfunction!(centroid, m)?)?;
+    m.add_function(wrap_pyfunction!(strip_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(strip_distance, m)?)?;
+    m.add_function(wrap_pyfunction!(aligned_phash, m)?)?;
+    m.add_function(wrap_pyfunction!(compare_files, m)?)?;
+    m.add_function(wrap_pyfunction!(compare_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(classify_pair, m)?)?;
+    m.add_function(wrap_pyfunction!(ensemble_distance, m)?)?;
+    m.add_function(wrap_pyfunction!(signature, m)?)?;
+    m.add_function(wrap_pyfunction!(signature_distance, m)?)?;
+    m.add_function(wrap_pyfunction!(compare_detailed, m)?)?;
+    m.add_function(wrap_pyfunction!(compare, m)?)?;
+    m.add_function(wrap_pyfunction!(render_diff, m)?)?;
+    m.add_function(wrap_pyfunction!(save_hashes, m)?)?;
+    m.add_function(wrap_pyfunction!(load_hashes, m)?)?;
+    m.add_function(wrap_pyfunction!(load_csv, m)?)?;
+    m.add_function(wrap_pyfunction!(packed_len, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_bits_is_lsb_first_with_zero_padding() {
+        let bits = [
+            true, false, false, false, false, false, false, false, false, true,
+        ];
+        assert_eq!(pack_bits(&bits), [0x01, 0x02]);
+        assert_eq!(pack_bits(&[]), Vec::<u8>::new());
+        assert_eq!(pack_bits(&[true; 8]), [0xff]);
+    }
+
+    #[test]
+    fn unpack_bits_inverts_pack_bits() {
+        let bits: Vec<bool> = (0..29).map(|i| i % 3 == 0 || i % 7 == 0).collect();
+        assert_eq!(unpack_bits(&pack_bits(&bits), bits.len()), bits);
+        assert_eq!(unpack_bits(&[0b1010_0101], 4), [true, false, true, false]);
+    }
+
+    #[test]
+    fn packed_len_rounds_up() {
+        assert_eq!(packed_len(8), 8);
+        assert_eq!(packed_len(5), 4);
+        assert_eq!(packed_len(1), 1);
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), 2.5);
+        assert_eq!(median(&mut [7.0]), 7.0);
+        assert_eq!(median(&mut [5.0, 5.0, 1.0, 9.0]), 5.0);
+    }
+
+    #[test]
+    fn collision_probability_matches_the_binomial_tail() {
+        assert_eq!(collision_probability(64, 64), 1.0);
+        assert_eq!(collision_probability(4, 0), 1.0 / 16.0);
+        // (1 + 4 + 6) / 16
+        assert!((collision_probability(4, 2) - 11.0 / 16.0).abs() < 1e-12);
+        // Symmetric around n / 2: P(X <= 31) + P(X <= 32) = 1 for 64 bits.
+        let sum = collision_probability(64, 31) + collision_probability(64, 32);
+        assert!((sum - 1.0).abs() < 1e-9);
+        // Deep in the tail without underflowing to zero.
+        let tiny = collision_probability(1024, 10);
+        assert!(tiny > 0.0 && tiny < 1e-250);
+    }
+
+    #[test]
+    fn zigzag_follows_jpeg_order() {
+        let order: Vec<(usize, usize)> = zigzag(3).collect();
+        assert_eq!(
+            order,
+            [
+                (0, 0),
+                (0, 1),
+                (1, 0),
+                (2, 0),
+                (1, 1),
+                (0, 2),
+                (1, 2),
+                (2, 1),
+                (2, 2)
+            ]
+        );
+        assert_eq!(zigzag(8).count(), 64);
+        assert_eq!(zigzag(1).collect::<Vec<_>>(), [(0, 0)]);
+    }
+
+    #[test]
+    fn min_cost_assignment_finds_the_optimum() {
+        let cost = vec![vec![4, 1, 3], vec![2, 0, 5], vec![3, 2, 2]];
+        assert_eq!(min_cost_assignment(&cost), 5);
+        // More columns than rows.
+        assert_eq!(min_cost_assignment(&[vec![9, 2, 7], vec![1, 8, 6]]), 3);
+        assert_eq!(min_cost_assignment(&[vec![7]]), 7);
+    }
+
+    #[test]
+    fn csv_field_quotes_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn parse_csv_undoes_csv_field() {
+        let fields = ["a,b", "say \"hi\"", "two\nlines", "", "plain"];
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        let text = format!("{}\r\n\n{}\n", line.join(","), line.join(","));
+        let rows = parse_csv(&text);
+        assert_eq!(rows.len(), 2);
+        for row in rows {
+            assert_eq!(row, fields);
+        }
+        assert_eq!(parse_csv("x,y"), [["x", "y"]]);
+    }
+
+    #[test]
+    fn parse_grid_reads_both_forms() {
+        assert_eq!(parse_grid("16x9"), Some((16, 9)));
+        assert_eq!(parse_grid("8"), Some((8, 8)));
+        assert_eq!(parse_grid("8x"), None);
+        assert_eq!(parse_grid("x8"), None);
+        assert_eq!(parse_grid("eight"), None);
+    }
+
+    // Gradient with a deterministic speckle, so resizing lands on fractional values.
+    fn gray_image(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| {
+            let speckle = (x * 7919 + y * 104729) % 37;
+            image::Luma([((x * 5 + y * 3 + speckle) % 256) as u8])
+        })
+    }
+
+    #[test]
+    fn gray_and_gray_rgb_give_the_same_grid() {
+        let gray = DynamicImage::ImageLuma8(gray_image(97, 61));
+        let rgb = DynamicImage::ImageRgb8(gray.to_rgb8());
+        let variants = [
+            HashOptions::default(),
+            HashOptions {
+                linearize_srgb: true,
+                ..Default::default()
+            },
+            HashOptions {
+                blur: Some(1.5),
+                ..Default::default()
+            },
+            HashOptions {
+                equalize: true,
+                ..Default::default()
+            },
+        ];
+        let algos = [
+            Algorithm::Average,
+            Algorithm::Difference,
+            Algorithm::Perceptual,
+            Algorithm::Median,
+        ];
+        // Every hash is a function of the grid alone, so equal grids mean equal hashes.
+        for opts in &variants {
+            for algo in algos {
+                let a = algo.grid(gray.clone(), 8, opts);
+                let b = algo.grid(rgb.clone(), 8, opts);
+                assert_eq!(a, b, "{}", algo.name());
+            }
+        }
+    }
+
+    #[test]
+    fn linear_luma_keeps_gray_levels_of_gray_pixels() {
+        let gray = gray_image(16, 16);
+        let rgb = DynamicImage::ImageRgb8(DynamicImage::ImageLuma8(gray.clone()).to_rgb8());
+        let linear = linear_luma(&rgb);
+        for (px, lin) in gray.pixels().zip(linear.pixels()) {
+            let c = px.0[0] as f64 / 255.0;
+            let expected = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+            assert_eq!(lin.0[0], (expected * 255.0).round() as u8);
+        }
+    }
+}
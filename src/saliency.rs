@@ -0,0 +1,99 @@
+// Crop to the salient region of an image, found by spectral residual (Hou and Zhang).
+//
+// The log amplitude spectrum of natural images is close to a smooth curve, and what
+// stands out of it once locally averaged, the spectral residual, belongs to whatever
+// stands out of the scene. Transforming the residual back with the original phases gives
+// a saliency map, which is smoothed and thresholded into the bounding box of the subject.
+
+use image::{imageops::FilterType, DynamicImage, ImageBuffer, Luma};
+
+use crate::align::{dft2, Complex};
+
+// Side of the grid the saliency map is computed at.
+const GRID: usize = 64;
+
+// Pixels more salient than this many times the mean of the map belong to the subject.
+const THRESHOLD: f32 = 3.0;
+
+// Crops an image to the bounding box of its salient region. An image without a clear
+// subject, where nothing or nearly everything is salient, is returned untouched.
+pub(crate) fn saliency_crop(img: DynamicImage) -> DynamicImage {
+    let map = saliency_map(&img);
+    let mean = map.pixels().map(|px| px.0[0]).sum::<f32>() / (GRID * GRID) as f32;
+    if mean <= 0.0 {
+        return img;
+    }
+
+    let salient: Vec<(u32, u32)> = map
+        .enumerate_pixels()
+        .filter(|(_, _, px)| px.0[0] > THRESHOLD * mean)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    if salient.is_empty() {
+        return img;
+    }
+    let left = salient.iter().map(|(x, _)| *x).min().unwrap_or(0);
+    let right = salient.iter().map(|(x, _)| *x).max().unwrap_or(0) + 1;
+    let top = salient.iter().map(|(_, y)| *y).min().unwrap_or(0);
+    let bottom = salient.iter().map(|(_, y)| *y).max().unwrap_or(0) + 1;
+
+    // Grid cells back to image pixels, rounding outwards.
+    let (width, height) = (img.width() as u64, img.height() as u64);
+    let grid = GRID as u64;
+    let x0 = (left as u64 * width / grid) as u32;
+    let x1 = (right as u64 * width).div_ceil(grid) as u32;
+    let y0 = (top as u64 * height / grid) as u32;
+    let y1 = (bottom as u64 * height).div_ceil(grid) as u32;
+    if (x0, y0, x1, y1) == (0, 0, img.width(), img.height()) {
+        return img;
+    }
+    img.crop_imm(x0, y0, x1 - x0, y1 - y0)
+}
+
+// Smoothed spectral residual saliency of an image, over a `GRID` x `GRID` reduction.
+fn saliency_map(img: &DynamicImage) -> ImageBuffer<Luma<f32>, Vec<f32>> {
+    let gray = img
+        .resize_exact(GRID as u32, GRID as u32, FilterType::Triangle)
+        .to_luma8();
+    let grid: Vec<Complex> = gray.pixels().map(|px| (px.0[0] as f64, 0.0)).collect();
+    let spectrum = dft2(&grid, GRID, false);
+
+    let log_amplitude: Vec<f64> = spectrum
+        .iter()
+        .map(|(re, im)| (re * re + im * im).sqrt().max(1e-9).ln())
+        .collect();
+
+    // Residual against the 3x3 mean of the log amplitude, wrapping around the edges as
+    // the spectrum is periodic. Its exponential gives the new amplitude.
+    let residual: Vec<Complex> = (0..GRID * GRID)
+        .map(|i| {
+            let (x, y) = (i % GRID, i / GRID);
+            let mut local = 0.0;
+            for dy in [GRID - 1, 0, 1] {
+                for dx in [GRID - 1, 0, 1] {
+                    local += log_amplitude[(y + dy) % GRID * GRID + (x + dx) % GRID];
+                }
+            }
+            let amplitude = (log_amplitude[i] - local / 9.0).exp();
+            let (re, im) = spectrum[i];
+            let norm = (re * re + im * im).sqrt();
+            if norm > 0.0 {
+                (re / norm * amplitude, im / norm * amplitude)
+            } else {
+                (0.0, 0.0)
+            }
+        })
+        .collect();
+
+    // Scaled to at most 1.0, the range `blur` keeps floating point pixels in.
+    let saliency: Vec<f64> = dft2(&residual, GRID, true)
+        .iter()
+        .map(|(re, im)| re * re + im * im)
+        .collect();
+    let max = saliency.iter().copied().fold(0.0, f64::max);
+    let scale = if max > 0.0 { 1.0 / max } else { 0.0 };
+    let map = ImageBuffer::from_fn(GRID as u32, GRID as u32, |x, y| {
+        Luma([(saliency[y as usize * GRID + x as usize] * scale) as f32])
+    });
+    image::imageops::blur(&map, 2.5)
+}
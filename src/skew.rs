@@ -0,0 +1,95 @@
+// Tilt estimate for slightly rotated scans and photos.
+//
+// The edges of a document or a building run along two perpendicular directions. Per
+// pixel gradient directions are too noisy for a small tilt, as a line at a few degrees is
+// drawn as a staircase of perfectly horizontal runs, so the gradient energy is projected
+// instead: summed along the rows and the columns of the image sheared by each candidate
+// angle. At the tilt of the content its edges line up with the projection, which becomes
+// as peaked as it gets, as measured by the sum of its squares. Only tilts of up to
+// `MAX_SKEW` degrees are looked for.
+
+use image::{imageops::FilterType, DynamicImage, GrayImage};
+
+// Largest tilt, in degrees, that is corrected.
+const MAX_SKEW: f64 = 15.0;
+
+// Step of the coarse search over angles, then of its refinement, in degrees.
+const COARSE_STEP: f64 = 0.5;
+const FINE_STEP: f64 = 0.05;
+
+// Longest side the gradients are computed at.
+const ANALYSIS_SIDE: u32 = 400;
+
+// Tilt of the image content in degrees: lines that should be horizontal go down to the
+// right by this angle, vertical ones lean the same way. Zero when nothing stands out.
+pub(crate) fn estimate_skew(img: &DynamicImage) -> f64 {
+    let gray = if img.width().max(img.height()) > ANALYSIS_SIDE {
+        img.resize(ANALYSIS_SIDE, ANALYSIS_SIDE, FilterType::Triangle)
+            .to_luma8()
+    } else {
+        img.to_luma8()
+    };
+    let edges = strong_edges(&gray);
+    if edges.is_empty() {
+        return 0.0;
+    }
+
+    let search = |from: f64, to: f64, step: f64| {
+        let steps = ((to - from) / step).round() as i32;
+        (0..=steps)
+            .map(|i| from + i as f64 * step)
+            .map(|angle| (angle, sharpness(&edges, angle)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(0.0, |(angle, _)| angle)
+    };
+    let coarse = search(-MAX_SKEW, MAX_SKEW, COARSE_STEP);
+    search(coarse - COARSE_STEP, coarse + COARSE_STEP, FINE_STEP)
+}
+
+// How peaked the row and column projections of the edges are once sheared by `angle`.
+fn sharpness(edges: &[(f64, f64, f64, f64)], angle: f64) -> f64 {
+    let tan = angle.to_radians().tan();
+    let mut rows: Vec<f64> = Vec::new();
+    let mut columns: Vec<f64> = Vec::new();
+    let add = |bins: &mut Vec<f64>, at: f64, weight: f64| {
+        // Offset so that every sheared coordinate is positive.
+        let bin = (at + 2.0 * ANALYSIS_SIDE as f64).round().max(0.0) as usize;
+        if bin >= bins.len() {
+            bins.resize(bin + 1, 0.0);
+        }
+        bins[bin] += weight;
+    };
+
+    for &(x, y, horizontal, vertical) in edges {
+        add(&mut rows, y - x * tan, horizontal);
+        add(&mut columns, x + y * tan, vertical);
+    }
+    rows.iter().chain(&columns).map(|bin| bin * bin).sum()
+}
+
+// Position and strength of the horizontal and vertical edges of the pixels with an above
+// average Sobel gradient.
+fn strong_edges(gray: &GrayImage) -> Vec<(f64, f64, f64, f64)> {
+    let (width, height) = gray.dimensions();
+    let at = |x: u32, y: u32| gray.get_pixel(x, y).0[0] as f64;
+    let mut edges = Vec::new();
+
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            // A horizontal edge has a vertical gradient.
+            edges.push((x as f64, y as f64, gy.abs(), gx.abs()));
+        }
+    }
+
+    let mean = edges.iter().map(|e| e.2 + e.3).sum::<f64>() / edges.len().max(1) as f64;
+    edges.retain(|e| e.2 + e.3 > mean && mean > 0.0);
+    edges
+}
@@ -0,0 +1,66 @@
+// Log-magnitude spectrum used by the spectral perceptual hash.
+//
+// The magnitude of a Fourier transform does not depend on where a periodic pattern
+// starts, only on its period and orientation, so two tilings of the same texture at
+// different offsets have nearly the same spectrum. Requires the `fft` feature, without it
+// every call fails with a `PyValueError`.
+
+use image::GrayImage;
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+// `log(1 + |F[v][u]|)` of the 2D DFT of an image, for the `size` lowest horizontal
+// frequencies `u` and the `size` vertical frequencies `v` centered on zero, row by row
+// from the most negative `v`. Real images have symmetric spectra, so this half plane
+// holds all the information. The DC term comes first in the row of `v = 0`.
+#[cfg(feature = "fft")]
+pub(crate) fn log_magnitude(img: &GrayImage, size: u32) -> PyResult<Vec<f64>> {
+    use rustfft::{num_complex::Complex, FftPlanner};
+
+    let (width, height) = img.dimensions();
+    if size > width || size > height {
+        return Err(PyValueError::new_err("Image is smaller than the spectrum"));
+    }
+    let (width, height) = (width as usize, height as usize);
+
+    let mut planner = FftPlanner::<f64>::new();
+    let row_plan = planner.plan_fft_forward(width);
+    let col_plan = planner.plan_fft_forward(height);
+
+    // Rows first, keeping only the columns that are used.
+    let size = size as usize;
+    let rows: Vec<Vec<Complex<f64>>> = (0..height)
+        .map(|y| {
+            let mut row: Vec<Complex<f64>> = (0..width)
+                .map(|x| Complex::new(img.get_pixel(x as u32, y as u32).0[0] as f64, 0.0))
+                .collect();
+            row_plan.process(&mut row);
+            row.truncate(size);
+            row
+        })
+        .collect();
+
+    let cols: Vec<Vec<Complex<f64>>> = (0..size)
+        .map(|u| {
+            let mut col: Vec<Complex<f64>> = rows.iter().map(|row| row[u]).collect();
+            col_plan.process(&mut col);
+            col
+        })
+        .collect();
+
+    let half = size as isize / 2;
+    let mut out = Vec::with_capacity(size * size);
+    for v in -half..size as isize - half {
+        let row = v.rem_euclid(height as isize) as usize;
+        for col in &cols {
+            out.push(col[row].norm().ln_1p());
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(not(feature = "fft"))]
+pub(crate) fn log_magnitude(_img: &GrayImage, _size: u32) -> PyResult<Vec<f64>> {
+    Err(PyValueError::new_err(
+        "Spectral hashing is not enabled, build with the `fft` feature.",
+    ))
+}
@@ -0,0 +1,200 @@
+// ThumbHash encoding, a compact placeholder from which a blurry preview can be decoded.
+//
+// This follows the reference encoder by Evan Wallace (https://evanw.github.io/thumbhash/)
+// byte for byte, so the blobs can be decoded by any of its implementations. The image is
+// split into luminance, two chroma channels and alpha, each reduced to a few low
+// frequency DCT terms quantized to 4 bits.
+
+use std::f64::consts::PI;
+
+use image::DynamicImage;
+
+// The encoder only needs a tiny thumbnail, larger inputs are scaled down to fit.
+const MAX_SIDE: u32 = 100;
+
+// Low frequency DCT terms of one channel: the DC term, the AC terms normalized to [0, 1]
+// and the scale they were normalized by.
+struct Channel {
+    dc: f64,
+    ac: Vec<f64>,
+    scale: f64,
+}
+
+fn encode_channel(values: &[f64], w: usize, h: usize, nx: usize, ny: usize) -> Channel {
+    let mut dc = 0.0;
+    let mut ac = Vec::new();
+    let mut scale: f64 = 0.0;
+    for cy in 0..ny {
+        // Only the triangle of terms below the anti-diagonal is kept.
+        let mut cx = 0;
+        while cx * ny < nx * (ny - cy) {
+            let fx: Vec<f64> = (0..w)
+                .map(|x| (PI / w as f64 * cx as f64 * (x as f64 + 0.5)).cos())
+                .collect();
+            let mut f = 0.0;
+            for y in 0..h {
+                let fy = (PI / h as f64 * cy as f64 * (y as f64 + 0.5)).cos();
+                for x in 0..w {
+                    f += values[x + y * w] * fx[x] * fy;
+                }
+            }
+            f /= (w * h) as f64;
+
+            if cx > 0 || cy > 0 {
+                ac.push(f);
+                scale = scale.max(f.abs());
+            } else {
+                dc = f;
+            }
+            cx += 1;
+        }
+    }
+
+    if scale > 0.0 {
+        for f in ac.iter_mut() {
+            *f = 0.5 + 0.5 / scale * *f;
+        }
+    }
+
+    Channel { dc, ac, scale }
+}
+
+// Encodes the image into a ThumbHash blob of at most 25 bytes.
+pub(crate) fn encode(img: &DynamicImage) -> Vec<u8> {
+    let thumb = if img.width() > MAX_SIDE || img.height() > MAX_SIDE {
+        img.thumbnail(MAX_SIDE, MAX_SIDE)
+    } else {
+        img.clone()
+    };
+    let (w, h) = (thumb.width() as usize, thumb.height() as usize);
+    let rgba: Vec<[f64; 4]> = thumb
+        .to_rgba8()
+        .pixels()
+        .map(|p| p.0.map(|c| c as f64 / 255.0))
+        .collect();
+
+    // Average color, weighted by alpha.
+    let (mut avg_r, mut avg_g, mut avg_b, mut avg_a) = (0.0, 0.0, 0.0, 0.0);
+    for [r, g, b, a] in &rgba {
+        avg_r += a * r;
+        avg_g += a * g;
+        avg_b += a * b;
+        avg_a += a;
+    }
+    if avg_a > 0.0 {
+        avg_r /= avg_a;
+        avg_g /= avg_a;
+        avg_b /= avg_a;
+    }
+
+    // Fewer luminance terms are kept when the alpha channel takes up room.
+    let has_alpha = avg_a < (w * h) as f64;
+    let l_limit = if has_alpha { 5.0 } else { 7.0 };
+    let longest = w.max(h) as f64;
+    let lx = ((l_limit * w as f64 / longest).round() as usize).max(1);
+    let ly = ((l_limit * h as f64 / longest).round() as usize).max(1);
+
+    // Composite over the average color and convert to LPQA: luminance, yellow-blue,
+    // red-green and alpha.
+    let mut l = Vec::with_capacity(w * h);
+    let mut p = Vec::with_capacity(w * h);
+    let mut q = Vec::with_capacity(w * h);
+    let mut a = Vec::with_capacity(w * h);
+    for [pr, pg, pb, alpha] in &rgba {
+        let r = avg_r * (1.0 - alpha) + alpha * pr;
+        let g = avg_g * (1.0 - alpha) + alpha * pg;
+        let b = avg_b * (1.0 - alpha) + alpha * pb;
+        l.push((r + g + b) / 3.0);
+        p.push((r + g) / 2.0 - b);
+        q.push(r - g);
+        a.push(*alpha);
+    }
+
+    let l = encode_channel(&l, w, h, lx.max(3), ly.max(3));
+    let p = encode_channel(&p, w, h, 3, 3);
+    let q = encode_channel(&q, w, h, 3, 3);
+    let a = has_alpha.then(|| encode_channel(&a, w, h, 5, 5));
+
+    let is_landscape = w > h;
+    let header24 = (63.0 * l.dc).round() as u32
+        | ((31.5 + 31.5 * p.dc).round() as u32) << 6
+        | ((31.5 + 31.5 * q.dc).round() as u32) << 12
+        | ((31.0 * l.scale).round() as u32) << 18
+        | (has_alpha as u32) << 23;
+    let header16 = (if is_landscape { ly } else { lx }) as u32
+        | ((63.0 * p.scale).round() as u32) << 3
+        | ((63.0 * q.scale).round() as u32) << 9
+        | (is_landscape as u32) << 15;
+    let mut hash = vec![
+        header24 as u8,
+        (header24 >> 8) as u8,
+        (header24 >> 16) as u8,
+        header16 as u8,
+        (header16 >> 8) as u8,
+    ];
+    if let Some(a) = &a {
+        hash.push((15.0 * a.dc).round() as u8 | ((15.0 * a.scale).round() as u8) << 4);
+    }
+
+    // Two AC terms per byte, the first one in the low nibble.
+    let mut channels = vec![&l, &p, &q];
+    channels.extend(a.as_ref());
+    let ac_start = hash.len();
+    for (i, f) in channels.iter().flat_map(|c| &c.ac).enumerate() {
+        if i % 2 == 0 {
+            hash.push(0);
+        }
+        hash[ac_start + i / 2] |= ((15.0 * f).round() as u8) << ((i % 2) * 4);
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    fn header24(hash: &[u8]) -> u32 {
+        hash[0] as u32 | (hash[1] as u32) << 8 | (hash[2] as u32) << 16
+    }
+
+    #[test]
+    fn solid_color_header() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([128, 128, 128])));
+        let hash = encode(&img);
+        let header = header24(&hash);
+        // Luminance DC of 128 / 255, neutral chroma, no alpha.
+        assert_eq!(header & 63, 32);
+        assert_eq!(header >> 6 & 63, 32);
+        assert_eq!(header >> 12 & 63, 32);
+        assert_eq!(header >> 23 & 1, 0);
+        // Square, so not landscape, with the full 7 luminance terms.
+        let header16 = hash[3] as u32 | (hash[4] as u32) << 8;
+        assert_eq!(header16 & 7, 7);
+        assert_eq!(header16 >> 15, 0);
+    }
+
+    #[test]
+    fn alpha_is_flagged_and_encoded() {
+        let opaque = DynamicImage::ImageRgba8(RgbaImage::from_pixel(16, 8, Rgba([255; 4])));
+        let clear = DynamicImage::ImageRgba8(RgbaImage::from_fn(16, 8, |x, _| {
+            Rgba([255, 0, 0, if x < 8 { 255 } else { 0 }])
+        }));
+        let (opaque, clear) = (encode(&opaque), encode(&clear));
+        assert_eq!(header24(&opaque) >> 23, 0);
+        assert_eq!(header24(&clear) >> 23, 1);
+        // Landscape images set the top bit of the second header.
+        assert_eq!(opaque[4] >> 7, 1);
+    }
+
+    #[test]
+    fn blobs_are_small_and_deterministic() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(300, 200, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8])
+        }));
+        let hash = encode(&img);
+        assert!(hash.len() <= 25);
+        assert_eq!(hash, encode(&img));
+    }
+}
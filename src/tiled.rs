@@ -0,0 +1,97 @@
+// Incremental hashing of images too large to decode at once.
+//
+// Tiles are decoded one at a time and box-averaged into a canvas whose longest side is at
+// most `max_side`, so memory stays bounded by the canvas, never by the full image. When
+// the whole image fits in the canvas, its pixels are kept exactly and the hash is the one
+// the image would get if it were decoded in one piece.
+
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{Algorithm, HashOptions, ImageHash};
+
+// Longest side of the canvas tiles are accumulated into, unless the caller picks one.
+const DEFAULT_MAX_SIDE: u32 = 1024;
+
+#[pyclass]
+pub(crate) struct TiledHasher {
+    width: u32,
+    height: u32,
+    canvas_width: u32,
+    canvas_height: u32,
+    // Per canvas cell, the RGBA sums of the pixels that fell into it and their count.
+    sums: Vec<[f64; 4]>,
+    counts: Vec<u32>,
+}
+
+#[pymethods]
+impl TiledHasher {
+    // Prepares to receive the tiles of a `width` x `height` image.
+    #[new]
+    fn new(width: u32, height: u32, max_side: Option<u32>) -> PyResult<TiledHasher> {
+        let max_side = max_side.unwrap_or(DEFAULT_MAX_SIDE);
+        if width == 0 || height == 0 || max_side == 0 {
+            return Err(PyValueError::new_err("Image and canvas must not be empty"));
+        }
+
+        let scale = (max_side as f64 / width.max(height) as f64).min(1.0);
+        let canvas_width = ((width as f64 * scale).round() as u32).max(1);
+        let canvas_height = ((height as f64 * scale).round() as u32).max(1);
+        let cells = (canvas_width * canvas_height) as usize;
+
+        Ok(TiledHasher {
+            width,
+            height,
+            canvas_width,
+            canvas_height,
+            sums: vec![[0.0; 4]; cells],
+            counts: vec![0; cells],
+        })
+    }
+
+    // Adds an encoded tile whose top left corner is at `(x, y)` in the full image.
+    fn feed(&mut self, tile_bytes: Vec<u8>, x: u32, y: u32) -> PyResult<()> {
+        let tile = match image::load_from_memory(&tile_bytes) {
+            Ok(tile) => tile,
+            Err(_e) => return Err(PyValueError::new_err("Cannot open tile.")),
+        };
+        let (tile_width, tile_height) = tile.dimensions();
+        if x as u64 + tile_width as u64 > self.width as u64
+            || y as u64 + tile_height as u64 > self.height as u64
+        {
+            return Err(PyValueError::new_err("Tile is outside of the image"));
+        }
+
+        for (tx, ty, px) in tile.to_rgba8().enumerate_pixels() {
+            let cx = (x + tx) as u64 * self.canvas_width as u64 / self.width as u64;
+            let cy = (y + ty) as u64 * self.canvas_height as u64 / self.height as u64;
+            let cell = (cy * self.canvas_width as u64 + cx) as usize;
+            for (sum, ch) in self.sums[cell].iter_mut().zip(px.0) {
+                *sum += ch as f64;
+            }
+            self.counts[cell] += 1;
+        }
+
+        Ok(())
+    }
+
+    // Hashes the accumulated canvas with `algo` and its default options. Every part of
+    // the image must have been fed.
+    fn finalize(&self, hash_size: u32, algo: String) -> PyResult<ImageHash> {
+        let algo = Algorithm::parse(&algo)?;
+        if self.counts.contains(&0) {
+            return Err(PyValueError::new_err("Image is not fully covered by tiles"));
+        }
+
+        let canvas = RgbaImage::from_fn(self.canvas_width, self.canvas_height, |cx, cy| {
+            let cell = (cy * self.canvas_width + cx) as usize;
+            let count = self.counts[cell] as f64;
+            image::Rgba(self.sums[cell].map(|sum| (sum / count).round() as u8))
+        });
+        algo.hash_image(
+            DynamicImage::ImageRgba8(canvas),
+            hash_size,
+            &HashOptions::default(),
+        )
+    }
+}
@@ -0,0 +1,118 @@
+// Frame extraction from video files.
+//
+// Decoding is delegated to the `ffmpeg` and `ffprobe` command line tools, which must be
+// on `PATH`. Without the `video` feature every call fails with a `PyValueError`.
+
+use image::DynamicImage;
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+#[cfg(feature = "video")]
+use std::{
+    io::ErrorKind,
+    process::{Command, Output},
+};
+
+// Decodes the frame shown at `timestamp_sec` seconds into the video.
+#[cfg(feature = "video")]
+pub(crate) fn extract_frame(fpath: &str, timestamp_sec: f64) -> PyResult<DynamicImage> {
+    let duration = probe_duration(fpath)?;
+    if !(0.0..=duration).contains(&timestamp_sec) {
+        return Err(PyValueError::new_err(format!(
+            "Timestamp {} is outside of the video duration ({}).",
+            timestamp_sec, duration
+        )));
+    }
+
+    let timestamp = timestamp_sec.to_string();
+    let input = input_path(fpath);
+    let output = run(
+        "ffmpeg",
+        &[
+            "-v",
+            "error",
+            "-ss",
+            &timestamp,
+            "-i",
+            &input,
+            "-frames:v",
+            "1",
+            "-f",
+            "image2pipe",
+            "-c:v",
+            "png",
+            "-",
+        ],
+    )?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(PyValueError::new_err("Cannot decode video frame."));
+    }
+
+    match image::load_from_memory(&output.stdout) {
+        Ok(im) => Ok(im),
+        Err(_e) => Err(PyValueError::new_err("Cannot decode video frame.")),
+    }
+}
+
+#[cfg(not(feature = "video"))]
+pub(crate) fn extract_frame(_fpath: &str, _timestamp_sec: f64) -> PyResult<DynamicImage> {
+    Err(PyValueError::new_err(
+        "Video support is not enabled, build with the `video` feature.",
+    ))
+}
+
+// Reads the duration of a video in seconds.
+#[cfg(feature = "video")]
+fn probe_duration(fpath: &str) -> PyResult<f64> {
+    let input = input_path(fpath);
+    let output = run(
+        "ffprobe",
+        &[
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+            "-i",
+            &input,
+        ],
+    )?;
+
+    let duration = if output.status.success() {
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<f64>()
+            .ok()
+    } else {
+        None
+    };
+
+    match duration {
+        Some(duration) => Ok(duration),
+        None => Err(PyValueError::new_err("Cannot open video.")),
+    }
+}
+
+// Runs one of the ffmpeg tools, telling a missing tool apart from a failed run.
+#[cfg(feature = "video")]
+fn run(tool: &str, args: &[&str]) -> PyResult<Output> {
+    match Command::new(tool).args(args).output() {
+        Ok(output) => Ok(output),
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(PyValueError::new_err(format!(
+            "`{}` was not found, video support needs ffmpeg installed and on PATH.",
+            tool
+        ))),
+        Err(_e) => Err(PyValueError::new_err(format!("Cannot run `{}`.", tool))),
+    }
+}
+
+// `fpath` as given to `-i`. ffmpeg reads `-` as standard input, so a relative path
+// starting with a dash is spelled from the current directory instead.
+#[cfg(feature = "video")]
+fn input_path(fpath: &str) -> String {
+    if fpath.starts_with('-') {
+        format!("./{}", fpath)
+    } else {
+        fpath.to_string()
+    }
+}
@@ -0,0 +1,51 @@
+import pathlib
+
+import pytest
+
+from images import gray_level, scene, write_png
+
+DATA = pathlib.Path(__file__).parent / "data"
+
+
+@pytest.fixture
+def image_path(tmp_path):
+    return write_png(tmp_path / "image.png", 64, 48, scene)
+
+
+@pytest.fixture
+def shifted_path(tmp_path):
+    """The scene of `image_path` with its disc moved to the right."""
+    return write_png(tmp_path / "shifted.png", 64, 48, lambda x, y: scene(x, y, 16))
+
+
+@pytest.fixture
+def other_path(tmp_path):
+    """Unrelated content: a checkerboard."""
+    return write_png(
+        tmp_path / "other.png",
+        64,
+        48,
+        lambda x, y: (255, 255, 255) if (x // 8 + y // 8) % 2 else (0, 0, 0),
+    )
+
+
+@pytest.fixture
+def gray_path(tmp_path):
+    return write_png(tmp_path / "gray.png", 61, 47, gray_level, mode="L")
+
+
+@pytest.fixture
+def gray_rgb_path(tmp_path):
+    """`gray_path` stored as RGB."""
+
+    def pixel(x, y):
+        level = gray_level(x, y)
+        return (level, level, level)
+
+    return write_png(tmp_path / "gray_rgb.png", 61, 47, pixel)
+
+
+@pytest.fixture
+def jpeg_path():
+    """The scene of `image_path` as a JPEG."""
+    return str(DATA / "scene.jpg")
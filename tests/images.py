@@ -0,0 +1,124 @@
+"""Writers of the small synthetic images the tests hash."""
+
+import struct
+import zlib
+
+COLOR_TYPES = {"L": 0, "LA": 4, "RGB": 2, "RGBA": 6}
+
+
+def png_chunk(kind, data):
+    crc = zlib.crc32(kind + data) & 0xFFFFFFFF
+    return struct.pack(">I", len(data)) + kind + data + struct.pack(">I", crc)
+
+
+def write_png(path, width, height, pixel, mode="RGB", palette=None, chunks=()):
+    """Writes an 8-bit PNG whose pixel `(x, y)` is `pixel(x, y)`, an int for one channel
+    or a tuple of channels. With a `palette` of RGB tuples the image is indexed and
+    `pixel` returns indices. `chunks` are extra `(kind, data)` chunks written before the
+    pixels."""
+    raw = bytearray()
+    for y in range(height):
+        raw.append(0)
+        for x in range(width):
+            value = pixel(x, y)
+            raw.extend((value,) if isinstance(value, int) else value)
+
+    color_type = 3 if palette else COLOR_TYPES[mode]
+    header = struct.pack(">IIBBBBB", width, height, 8, color_type, 0, 0, 0)
+    with open(path, "wb") as f:
+        f.write(b"\x89PNG\r\n\x1a\n")
+        f.write(png_chunk(b"IHDR", header))
+        if palette:
+            f.write(png_chunk(b"PLTE", bytes(c for color in palette for c in color)))
+        for kind, data in chunks:
+            f.write(png_chunk(kind, data))
+        f.write(png_chunk(b"IDAT", zlib.compress(bytes(raw))))
+        f.write(png_chunk(b"IEND", b""))
+    return str(path)
+
+
+def write_apng(path, width, height, frames, mode="L"):
+    """Writes an animated PNG with one full-size frame per `pixel(x, y)` function in
+    `frames`, each shown for a tenth of a second."""
+
+    def pixels(pixel):
+        raw = bytearray()
+        for y in range(height):
+            raw.append(0)
+            for x in range(width):
+                value = pixel(x, y)
+                raw.extend((value,) if isinstance(value, int) else value)
+        return zlib.compress(bytes(raw))
+
+    header = struct.pack(">IIBBBBB", width, height, 8, COLOR_TYPES[mode], 0, 0, 0)
+    sequence = 0
+    with open(path, "wb") as f:
+        f.write(b"\x89PNG\r\n\x1a\n")
+        f.write(png_chunk(b"IHDR", header))
+        f.write(png_chunk(b"acTL", struct.pack(">II", len(frames), 0)))
+        for i, pixel in enumerate(frames):
+            # Placed at the origin, shown for 1/10 s, no disposal or blending.
+            control = struct.pack(
+                ">IIIIIHHBB", sequence, width, height, 0, 0, 1, 10, 0, 0
+            )
+            f.write(png_chunk(b"fcTL", control))
+            sequence += 1
+            if i == 0:
+                f.write(png_chunk(b"IDAT", pixels(pixel)))
+            else:
+                data = struct.pack(">I", sequence) + pixels(pixel)
+                f.write(png_chunk(b"fdAT", data))
+                sequence += 1
+        f.write(png_chunk(b"IEND", b""))
+    return str(path)
+
+
+def write_tiff(path, pages):
+    """Writes an uncompressed little endian TIFF with one page per `(width, height,
+    photometric, samples)`, `samples` holding 8-bit channels row by row. Photometric 2
+    is RGB and 5 is CMYK."""
+    out = bytearray(b"II*\x00\x00\x00\x00\x00")
+    next_offset_at = 4
+    for width, height, photometric, samples in pages:
+        channels = len(samples) // (width * height)
+        data_at = len(out)
+        out += samples
+        out += b"\x00" * (len(out) % 2)
+        bits_at = len(out)
+        out += struct.pack(f"<{channels}H", *[8] * channels)
+        tags = [
+            (256, 3, 1, width),
+            (257, 3, 1, height),
+            (258, 3, channels, bits_at),
+            (259, 3, 1, 1),
+            (262, 3, 1, photometric),
+            (273, 4, 1, data_at),
+            (277, 3, 1, channels),
+            (278, 3, 1, height),
+            (279, 4, 1, len(samples)),
+            (284, 3, 1, 1),
+        ]
+        struct.pack_into("<I", out, next_offset_at, len(out))
+        out += struct.pack("<H", len(tags))
+        for tag, kind, count, value in tags:
+            if kind == 3 and count == 1:
+                out += struct.pack("<HHIHH", tag, kind, count, value, 0)
+            else:
+                out += struct.pack("<HHII", tag, kind, count, value)
+        next_offset_at = len(out)
+        out += b"\x00\x00\x00\x00"
+    with open(path, "wb") as f:
+        f.write(out)
+    return str(path)
+
+
+def scene(x, y, shift=0):
+    """Diagonal gradients with a bright disc, enough structure for every bit to
+    matter."""
+    if (x - 40 - shift) ** 2 + (y - 24) ** 2 < 12**2:
+        return (240, 230, 200)
+    return ((x * 2 + y) % 256, (y * 3) % 256, (255 - x * 2) % 256)
+
+
+def gray_level(x, y):
+    return (x * 5 + y * 3 + (x * 7919 + y * 104729) % 37) % 256
@@ -0,0 +1,121 @@
+import io
+import struct
+import tarfile
+import zipfile
+
+import pytest
+
+import dif
+
+
+@pytest.fixture(autouse=True)
+def require_archive_feature(tmp_path):
+    try:
+        dif.hash_archive(str(tmp_path / "missing.zip"), 8, "ahash")
+    except ValueError as e:
+        if "not enabled" in str(e):
+            pytest.skip("built without the `archive` feature")
+
+
+def test_zip_entries(tmp_path, image_path):
+    path = tmp_path / "images.zip"
+    with zipfile.ZipFile(path, "w") as archive:
+        archive.write(image_path, "stored.png", compress_type=zipfile.ZIP_STORED)
+        archive.write(image_path, "deflated.png", compress_type=zipfile.ZIP_DEFLATED)
+        archive.writestr("notes.txt", "not an image")
+    hashes = dif.hash_archive(str(path), 8, "ahash")
+    expected = dif.ahash(image_path, 8).to_hex()
+    assert [(name, h.to_hex()) for name, h in hashes] == [
+        ("stored.png", expected),
+        ("deflated.png", expected),
+    ]
+
+
+def test_gzipped_tar_entries(tmp_path, image_path):
+    path = tmp_path / "images.tar.gz"
+    with tarfile.open(path, "w:gz", format=tarfile.PAX_FORMAT) as archive:
+        archive.add(image_path, "a" * 120 + ".png")
+    [(name, h)] = dif.hash_archive(str(path), 8, "ahash")
+    assert name == "a" * 120 + ".png"
+    assert h.to_hex() == dif.ahash(image_path, 8).to_hex()
+
+
+def test_zip64_end_of_directory(tmp_path):
+    # More than 65535 entries need the Zip64 end of central directory record.
+    path = tmp_path / "many.zip"
+    with zipfile.ZipFile(path, "w") as archive:
+        for i in range(0x10000 + 1):
+            archive.writestr(f"{i}.txt", b"")
+    assert dif.hash_archive(str(path), 8, "ahash") == []
+
+
+def zip64_archive(data, name=b"image.png"):
+    """A stored single-entry zip whose central directory keeps its sizes and offset in
+    the Zip64 extra field."""
+    size = len(data)
+    local = struct.pack(
+        "<4s5H3L2H", b"PK\x03\x04", 45, 0, 0, 0, 0, 0, size, size, len(name), 0
+    )
+    extra = struct.pack("<2H3Q", 1, 24, size, size, 0)
+    central = struct.pack(
+        "<4s6H3L5H2L",
+        b"PK\x01\x02",
+        45,
+        45,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0xFFFFFFFF,
+        0xFFFFFFFF,
+        len(name),
+        len(extra),
+        0,
+        0,
+        0,
+        0,
+        0xFFFFFFFF,
+    )
+    body = local + name + data
+    directory = central + name + extra
+    end = struct.pack(
+        "<4s4H2LH", b"PK\x05\x06", 0, 0, 1, 1, len(directory), len(body), 0
+    )
+    return body + directory + end
+
+
+def test_zip64_extra_field(tmp_path, image_path):
+    data = open(image_path, "rb").read()
+    path = tmp_path / "zip64.zip"
+    path.write_bytes(zip64_archive(data))
+    [(name, h)] = dif.hash_archive(str(path), 8, "ahash")
+    assert name == "image.png"
+    assert h.to_hex() == dif.ahash(image_path, 8).to_hex()
+
+
+def test_oversized_directory_is_rejected(tmp_path, image_path):
+    path = tmp_path / "bad.zip"
+    buffer = io.BytesIO()
+    with zipfile.ZipFile(buffer, "w") as archive:
+        archive.write(image_path, "image.png")
+    data = bytearray(buffer.getvalue())
+    # Claim a central directory far larger than the file.
+    end = data.rindex(b"PK\x05\x06")
+    data[end + 12 : end + 16] = struct.pack("<L", 0x7FFFFFFF)
+    path.write_bytes(bytes(data))
+    with pytest.raises(ValueError, match="Invalid or unsupported archive"):
+        dif.hash_archive(str(path), 8, "ahash")
+
+
+def test_oversized_entry_is_skipped(tmp_path, image_path):
+    path = tmp_path / "bomb.zip"
+    buffer = io.BytesIO()
+    with zipfile.ZipFile(buffer, "w", zipfile.ZIP_DEFLATED) as archive:
+        archive.write(image_path, "image.png")
+    data = bytearray(buffer.getvalue())
+    # Declare an uncompressed size deflate cannot reach from this input.
+    central = data.rindex(b"PK\x01\x02")
+    data[central + 24 : central + 28] = struct.pack("<L", 0xFFFFFFF0)
+    path.write_bytes(bytes(data))
+    assert dif.hash_archive(str(path), 8, "ahash") == [("image.png", None)]
@@ -0,0 +1,114 @@
+import math
+import random
+
+import pytest
+
+import dif
+
+
+def bits_hash(size, positions):
+    return dif.ImageHash([i in positions for i in range(size * size)], size)
+
+
+@pytest.mark.parametrize("bits, threshold", [(4, 0), (4, 1), (8, 3), (16, 5)])
+def test_collision_probability_is_the_binomial_tail(bits, threshold):
+    expected = sum(math.comb(bits, k) for k in range(threshold + 1)) / 2**bits
+    assert dif.collision_probability(bits, threshold) == pytest.approx(expected)
+
+
+def test_collision_probability_bounds():
+    assert dif.collision_probability(64, 64) == 1.0
+    assert dif.collision_probability(64, 100) == 1.0
+    assert dif.collision_probability(64, 32) > 0.5
+    assert 0.0 < dif.collision_probability(256, 10) < 1e-50
+
+
+def test_bit_frequency():
+    hashes = [bits_hash(2, {0, 1}), bits_hash(2, {1}), bits_hash(2, {1, 3})]
+    assert dif.bit_frequency(hashes) == [1, 3, 0, 1]
+    assert dif.bit_frequency([]) == []
+    with pytest.raises(ValueError):
+        dif.bit_frequency([bits_hash(2, set()), bits_hash(3, set())])
+
+
+def test_centroid_is_the_majority_vote():
+    members = [
+        bits_hash(4, {0, 1, 2, 3}),
+        bits_hash(4, {0, 1, 2, 3, 9}),
+        bits_hash(4, {0, 1, 2}),
+        bits_hash(4, {0, 1, 3, 15}),
+    ]
+    center = dif.centroid(members)
+    # Bits 2 and 3 are set in three of four hashes, 9 and 15 in one.
+    assert center.bool_values == bits_hash(4, {0, 1, 2, 3}).bool_values
+    assert all(center.distance(member) <= 2 for member in members)
+
+
+def test_centroid_ties_stay_unset():
+    center = dif.centroid([bits_hash(2, {0}), bits_hash(2, {1})])
+    assert center.bool_values == [False] * 4
+
+
+def test_centroid_validates_its_input():
+    with pytest.raises(ValueError):
+        dif.centroid([])
+    with pytest.raises(ValueError):
+        dif.centroid([bits_hash(2, set()), bits_hash(3, set())])
+
+
+def test_assignment_distance_beats_greedy_matching():
+    a = [bits_hash(4, set()), bits_hash(4, {0, 4, 5})]
+    b = [bits_hash(4, {0}), bits_hash(4, {2, 3})]
+    # Greedy takes the closest pair a[0]-b[0] (1) first, leaving a[1]-b[1] (5).
+    assert a[0].distance(b[0]) + a[1].distance(b[1]) == 6
+    assert dif.assignment_distance(a, b) == 4
+    assert dif.assignment_distance(b, a) == 4
+
+
+def test_assignment_distance_leaves_extra_hashes_unmatched():
+    a = [bits_hash(4, {1})]
+    b = [bits_hash(4, {7, 8, 9}), bits_hash(4, {1, 2})]
+    assert dif.assignment_distance(a, b) == 1
+    with pytest.raises(ValueError):
+        dif.assignment_distance([], b)
+
+
+def test_merge_galleries_returns_novel_indices():
+    existing = [bits_hash(4, {0, 1}), bits_hash(4, {8, 9, 10})]
+    incoming = [
+        bits_hash(4, {0, 1, 2}),
+        bits_hash(4, {4, 5, 6, 7, 12}),
+        bits_hash(4, {8, 9, 10}),
+        bits_hash(4, {13, 14, 15}),
+    ]
+    assert dif.merge_galleries(existing, incoming, 1) == [1, 3]
+    assert dif.merge_galleries(existing, incoming, 0) == [0, 1, 3]
+    assert dif.merge_galleries([], incoming, 5) == [0, 1, 2, 3]
+
+
+def test_learn_bit_weights_gives_a_noisy_bit_a_low_weight():
+    rng = random.Random(99)
+    noisy = 5
+    pairs = []
+    for i in range(400):
+        bits = [rng.random() < 0.5 for _ in range(16)]
+        duplicate = i % 2 == 0
+        if duplicate:
+            other = list(bits)
+            other[noisy] = rng.random() < 0.5
+        else:
+            other = [rng.random() < 0.5 for _ in range(16)]
+        pairs.append((dif.ImageHash(bits, 4), dif.ImageHash(other, 4), duplicate))
+
+    weights = dif.learn_bit_weights(pairs)
+    assert len(weights) == 16
+    assert all(weight >= 0.0 for weight in weights)
+    others = [w for i, w in enumerate(weights) if i != noisy]
+    assert weights[noisy] < min(others) / 4
+
+
+def test_learn_bit_weights_validates_its_input():
+    with pytest.raises(ValueError):
+        dif.learn_bit_weights([])
+    with pytest.raises(ValueError):
+        dif.learn_bit_weights([(bits_hash(2, set()), bits_hash(3, set()), True)])
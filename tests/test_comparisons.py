@@ -0,0 +1,209 @@
+import shutil
+
+import pytest
+
+import dif
+from images import scene, write_png
+
+
+def noisy_scene(x, y):
+    """`scene` with a faint deterministic noise, a near-duplicate of it."""
+    r, g, b = scene(x, y)
+    noise = (x * 31 + y * 17) % 5 - 2
+    return tuple(min(255, max(0, c + noise)) for c in (r, g, b))
+
+
+def panorama(x, y):
+    return ((x * 7 + (y // 4) * 29) % 256, (x * x // 16 + y * 3) % 256, (x // 3) % 256)
+
+
+@pytest.fixture
+def near_path(tmp_path):
+    return write_png(tmp_path / "near.png", 64, 48, noisy_scene)
+
+
+@pytest.fixture
+def copy_path(tmp_path, image_path):
+    return str(shutil.copy(image_path, tmp_path / "copy.png"))
+
+
+def test_find_duplicates(tmp_path, image_path, near_path, other_path):
+    groups = dif.find_duplicates([image_path, other_path, near_path], 8, "phash", 6)
+    assert [sorted(group) for group in groups] == [sorted([image_path, near_path])]
+
+
+def test_find_duplicates_ignores_unreadable_files(tmp_path, image_path, near_path):
+    broken = tmp_path / "broken.png"
+    broken.write_bytes(b"not an image")
+    paths = [str(broken), image_path, near_path]
+    assert len(dif.find_duplicates(paths, 8, "ahash", 6)) == 1
+
+
+@pytest.mark.parametrize("max_inflight", [1, 2, 64])
+def test_find_duplicates_max_inflight(
+    image_path, near_path, other_path, copy_path, max_inflight
+):
+    paths = [image_path, other_path, near_path, copy_path]
+    expected = dif.find_duplicates(paths, 8, "dhash", 6)
+    assert dif.find_duplicates(paths, 8, "dhash", 6, max_inflight) == expected
+
+
+def test_find_duplicates_rejects_zero_inflight(image_path):
+    with pytest.raises(ValueError):
+        dif.find_duplicates([image_path], 8, "ahash", 6, 0)
+
+
+def test_diversity_report(tmp_path):
+    similar = tmp_path / "similar"
+    varied = tmp_path / "varied"
+    similar.mkdir()
+    varied.mkdir()
+    for i in range(4):
+        write_png(similar / f"{i}.png", 64, 48, lambda x, y: scene(x, y, i))
+    write_png(varied / "scene.png", 64, 48, scene)
+    write_png(varied / "panorama.png", 64, 48, panorama)
+    write_png(varied / "stripes.png", 64, 48, lambda x, y: 255 * (x // 4 % 2), "L")
+    write_png(varied / "rings.png", 64, 48, lambda x, y: (x * x + y * y) % 256, "L")
+    (varied / "notes.txt").write_text("not an image")
+
+    low = dif.diversity_report(str(similar), 8, "phash")
+    high = dif.diversity_report(str(varied), 8, "phash")
+    assert 0.0 <= low < high <= 1.0
+
+
+def test_diversity_report_needs_two_images(tmp_path):
+    write_png(tmp_path / "only.png", 8, 8, scene)
+    with pytest.raises(ValueError):
+        dif.diversity_report(str(tmp_path), 8, "ahash")
+
+
+def test_classify_pair(image_path, copy_path, near_path, other_path):
+    assert dif.classify_pair(image_path, copy_path, 8, 6) == "identical"
+    assert dif.classify_pair(image_path, near_path, 8, 6) == "near-duplicate"
+    assert dif.classify_pair(image_path, other_path, 8, 6) == "different"
+
+
+def test_classify_pair_identical_files_are_not_decoded(tmp_path):
+    a = tmp_path / "a.bin"
+    a.write_bytes(b"same bytes, no image")
+    b = shutil.copy(a, tmp_path / "b.bin")
+    assert dif.classify_pair(str(a), str(b), 8, 0) == "identical"
+
+
+@pytest.mark.parametrize(
+    "algo, hash_file",
+    [
+        ("ahash", lambda path: dif.ahash(path, 8)),
+        ("dhash", lambda path: dif.dhash(path, 8)),
+        ("phash", lambda path: dif.phash(path, 8, 4)),
+        ("mhash", lambda path: dif.mhash(path, 8)),
+    ],
+)
+def test_compare_files_matches_separate_hashes(image_path, other_path, algo, hash_file):
+    expected = hash_file(image_path).distance(hash_file(other_path))
+    assert expected > 0
+    assert dif.compare_files(image_path, other_path, 8, algo) == expected
+
+
+def test_compare_files_identical_files_are_not_decoded(tmp_path):
+    a = tmp_path / "a.bin"
+    a.write_bytes(b"same bytes, no image")
+    b = shutil.copy(a, tmp_path / "b.bin")
+    assert dif.compare_files(str(a), str(b), 8, "ahash") == 0
+
+
+def test_compare_bytes(image_path, copy_path, other_path):
+    def read(path):
+        with open(path, "rb") as f:
+            return f.read()
+
+    data = read(image_path)
+    assert dif.compare_bytes(data, read(copy_path), 8, "dhash") == 0
+    expected = dif.dhash(image_path, 8).distance(dif.dhash(other_path, 8))
+    assert dif.compare_bytes(data, read(other_path), 8, "dhash") == expected
+    with pytest.raises(ValueError, match="image a"):
+        dif.compare_bytes(b"garbage", data, 8, "dhash")
+    with pytest.raises(ValueError, match="image b"):
+        dif.compare_bytes(data, b"garbage", 8, "dhash")
+
+
+def test_ensemble_distance(image_path, near_path, other_path):
+    near = dif.ensemble_distance(image_path, near_path, 8)
+    far = dif.ensemble_distance(image_path, other_path, 8)
+    assert dif.ensemble_distance(image_path, image_path, 8) == 0.0
+    assert near < far
+    assert 0.0 <= near and far <= 1.0
+
+
+def test_ensemble_distance_weights(image_path, other_path):
+    dhash_distance = dif.dhash(image_path, 8).distance(dif.dhash(other_path, 8))
+    only_dhash = dif.ensemble_distance(image_path, other_path, 8, (0.0, 1.0, 0.0))
+    assert only_dhash == pytest.approx(dhash_distance / 64)
+    with pytest.raises(ValueError):
+        dif.ensemble_distance(image_path, other_path, 8, (0.0, 0.0, 0.0))
+    with pytest.raises(ValueError):
+        dif.ensemble_distance(image_path, other_path, 8, (1.0, -1.0, 1.0))
+
+
+def test_ensemble_outvotes_a_coincidental_match(tmp_path):
+    # A flat gray image and a faintly textured one with a bright corner have nearly the
+    # same ahash, while dhash and phash tell them apart.
+    flat = write_png(tmp_path / "flat.png", 32, 32, lambda x, y: 128, mode="L")
+    corner = write_png(
+        tmp_path / "corner.png",
+        32,
+        32,
+        lambda x, y: 255 if x >= 28 and y >= 28 else 128 + (x * 3 + y * 5) % 7,
+        mode="L",
+    )
+    ahash_distance = dif.ahash(flat, 8).distance(dif.ahash(corner, 8)) / 64
+    assert ahash_distance < 0.25
+    assert dif.ensemble_distance(flat, corner, 8) > ahash_distance
+
+
+def test_compare_detailed(image_path, near_path):
+    detailed = dif.compare_detailed(image_path, near_path, 8)
+    assert set(detailed) == {"ahash", "dhash", "phash", "ensemble"}
+    scores = [detailed[key] for key in ("ahash", "dhash", "phash")]
+    assert min(scores) <= detailed["ensemble"] <= max(scores)
+    assert detailed["ensemble"] == pytest.approx(
+        dif.ensemble_distance(image_path, near_path, 8)
+    )
+
+
+def test_signature_penalizes_other_aspect_ratios(tmp_path, image_path, near_path):
+    # The scene cropped to a square: its resized hash stays close, its shape does not.
+    square = write_png(tmp_path / "square.png", 48, 48, lambda x, y: scene(x + 8, y))
+    hash_a, aspect_a = dif.signature(image_path, 8, "phash")
+    hash_n, aspect_n = dif.signature(near_path, 8, "phash")
+    hash_s, aspect_s = dif.signature(square, 8, "phash")
+    assert aspect_a == pytest.approx(64 / 48)
+    assert aspect_s == pytest.approx(1.0)
+    near = dif.signature_distance(hash_a, aspect_a, hash_n, aspect_n)
+    cropped = dif.signature_distance(hash_a, aspect_a, hash_s, aspect_s)
+    assert near < cropped
+    assert dif.signature_distance(hash_a, aspect_a, hash_s, aspect_s, 0.0) == float(
+        hash_a.distance(hash_s)
+    )
+    with pytest.raises(ValueError):
+        dif.signature_distance(hash_a, 0.0, hash_s, aspect_s)
+
+
+def test_strip_hash(tmp_path):
+    wide = write_png(tmp_path / "wide.png", 256, 32, panorama)
+    strips = dif.strip_hash(wide, 8, 8)
+    assert len(strips) == 8
+    assert all(strip.hash_size == 8 for strip in strips)
+
+
+def test_strip_distance_aligns_a_cropped_panorama(tmp_path):
+    wide = write_png(tmp_path / "wide.png", 256, 32, panorama)
+    # Strips 2 to 5 of the full panorama.
+    crop = write_png(tmp_path / "crop.png", 128, 32, lambda x, y: panorama(x + 64, y))
+    full = dif.strip_hash(wide, 8, 8)
+    part = dif.strip_hash(crop, 4, 8)
+    assert dif.strip_distance(full, part) == 0
+    assert dif.strip_distance(part, full) == 0
+    assert dif.strip_distance(full[:4], part) > 0
+    with pytest.raises(ValueError):
+        dif.strip_distance([], part)
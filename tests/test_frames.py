@@ -0,0 +1,21 @@
+import dif
+from images import scene, write_apng
+
+
+def test_frame_deltas_spike_at_scene_cuts(tmp_path):
+    # A slow pan across the scene, then a cut to the scene turned upside down.
+    def panned(shift):
+        return lambda x, y: scene(x + shift, y)
+
+    def flipped(shift):
+        return lambda x, y: scene(63 - x, 47 - y, shift)
+
+    frames = [panned(0), panned(1), panned(2), flipped(0), flipped(1)]
+    path = write_apng(tmp_path / "cut.png", 64, 48, frames, "RGB")
+    deltas = dif.frame_delta_hashes(path, 8)
+    assert len(deltas) == 4
+    assert deltas[2] > 4 * max(deltas[:2] + deltas[3:])
+
+
+def test_still_images_have_no_deltas(image_path):
+    assert dif.frame_delta_hashes(image_path, 8) == []
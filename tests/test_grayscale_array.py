@@ -0,0 +1,35 @@
+import sys
+
+import pytest
+
+import dif
+
+
+@pytest.mark.parametrize(
+    "algo, side", [("ahash", 8), ("mhash", 8), ("dhash", 9), ("phash", 32)]
+)
+def test_grayscale_array_shape(image_path, algo, side):
+    numpy = pytest.importorskip("numpy")
+    grid = dif.grayscale_array(image_path, 8, algo)
+    assert isinstance(grid, numpy.ndarray)
+    assert grid.dtype == numpy.uint8
+    assert grid.shape == (side, side)
+
+
+def test_grayscale_array_is_writable(image_path):
+    pytest.importorskip("numpy")
+    grid = dif.grayscale_array(image_path, 8, "ahash")
+    grid[0, 0] = 0
+
+
+def test_grayscale_array_thresholds_to_mhash(image_path):
+    numpy = pytest.importorskip("numpy")
+    grid = dif.grayscale_array(image_path, 8, "mhash").astype(float)
+    bits = (grid > numpy.median(grid)).flatten().tolist()
+    assert bits == dif.mhash(image_path, 8).bool_values
+
+
+def test_grayscale_array_without_numpy(image_path, monkeypatch):
+    monkeypatch.setitem(sys.modules, "numpy", None)
+    with pytest.raises(ImportError, match="numpy"):
+        dif.grayscale_array(image_path, 8, "ahash")
@@ -0,0 +1,111 @@
+import struct
+
+import pytest
+
+import dif
+from images import scene, write_png
+
+
+def png_size(data):
+    assert data[:8] == b"\x89PNG\r\n\x1a\n"
+    return struct.unpack(">II", data[16:24])
+
+
+def icon(x, y, fill):
+    """A disc on a transparent background, painted with `fill(x, y)`."""
+    inside = (x - 16) ** 2 + (y - 16) ** 2 < 100
+    return fill(x, y) + (255 if inside else 0,)
+
+
+def test_dhash_pair(image_path):
+    horizontal, vertical = dif.dhash_pair(image_path, 8)
+    assert horizontal.values == dif.dhash(image_path, 8).values
+    assert len(vertical) == 64
+    assert vertical.values != horizontal.values
+
+
+def test_vertical_dhash_is_the_horizontal_one_transposed(tmp_path):
+    a = write_png(tmp_path / "a.png", 40, 30, scene)
+    b = write_png(tmp_path / "b.png", 30, 40, lambda x, y: scene(y, x))
+    _, vertical = dif.dhash_pair(a, 8)
+    horizontal, _ = dif.dhash_pair(b, 8)
+    transposed = [horizontal.bool_values[x * 8 + y] for y in range(8) for x in range(8)]
+    assert vertical.bool_values == transposed
+
+
+def test_alpha_hash_matches_silhouettes(tmp_path):
+    def red_icon(x, y):
+        return icon(x, y, lambda x, y: (255, 0, 0))
+
+    def textured_icon(x, y):
+        return icon(x, y, scene)
+
+    red = write_png(tmp_path / "red.png", 32, 32, red_icon, "RGBA")
+    textured = write_png(tmp_path / "textured.png", 32, 32, textured_icon, "RGBA")
+    assert dif.alpha_hash(red, 8).distance(dif.alpha_hash(textured, 8)) == 0
+    assert dif.ahash(red, 8).distance(dif.ahash(textured, 8)) > 0
+
+
+def test_alpha_hash_needs_an_alpha_channel(image_path):
+    with pytest.raises(ValueError, match="no alpha channel"):
+        dif.alpha_hash(image_path, 8)
+
+
+def test_hash_pyramid_matches_direct_hashes(image_path):
+    pyramid = dif.hash_pyramid(image_path, 3, 4, "dhash")
+    assert [h.hash_size for h in pyramid] == [4, 8, 16]
+    for h in pyramid:
+        assert h.values == dif.dhash(image_path, h.hash_size).values
+
+
+@pytest.mark.parametrize("levels, base_size", [(0, 8), (3, 0), (10, 16)])
+def test_hash_pyramid_validates_sizes(image_path, levels, base_size):
+    with pytest.raises(ValueError, match="Pyramid"):
+        dif.hash_pyramid(image_path, levels, base_size, "ahash")
+
+
+def test_ahash_custom(image_path):
+    calls = []
+
+    def mean_threshold(mean, pixels):
+        calls.append((mean, pixels))
+        return mean
+
+    h = dif.ahash_custom(image_path, 8, mean_threshold)
+    mean, pixels = calls[0]
+    assert len(pixels) == 64
+    assert mean == pytest.approx(sum(pixels) / 64)
+    assert h.bool_values == [v > mean for v in pixels]
+
+    low = dif.ahash_custom(image_path, 8, lambda mean, pixels: -1)
+    high = dif.ahash_custom(image_path, 8, lambda mean, pixels: 255)
+    assert low.bool_values == [True] * 64
+    assert high.bool_values == [False] * 64
+
+
+def test_ahash_custom_propagates_errors(image_path):
+    def broken(mean, pixels):
+        raise KeyError("threshold")
+
+    with pytest.raises(KeyError):
+        dif.ahash_custom(image_path, 8, broken)
+
+
+def test_thumbhash(image_path, shifted_path):
+    blob = dif.thumbhash(image_path)
+    assert isinstance(blob, list)
+    assert 5 <= len(blob) <= 25
+    assert all(0 <= b < 256 for b in blob)
+    assert dif.thumbhash(image_path) == blob
+    assert dif.thumbhash(shifted_path) != blob
+
+
+def test_hash_with_thumbnail(tmp_path, image_path):
+    h, png = dif.hash_with_thumbnail(image_path, 8, 16, "phash")
+    assert h.values == dif.phash(image_path, 8, 4).values
+    assert png_size(bytes(png)) == (16, 12)
+
+    tall = write_png(tmp_path / "tall.png", 20, 80, scene)
+    h, png = dif.hash_with_thumbnail(tall, 8, 40, "ahash")
+    assert h.values == dif.ahash(tall, 8).values
+    assert png_size(bytes(png)) == (10, 40)
@@ -0,0 +1,50 @@
+import pytest
+
+import dif
+
+
+def hash_with(algo, path, **options):
+    return dif.Hasher(dif.HashConfig(algo, 8, **options)).hash(path)
+
+
+@pytest.mark.parametrize(
+    "algo, free, options",
+    [
+        ("ahash", dif.ahash, {"center": True}),
+        ("ahash", dif.ahash, {"unpremultiply": True, "auto_deskew": True}),
+        ("dhash", dif.dhash, {"blur": 1.5, "snap_to_grid": 4}),
+        ("dhash", dif.dhash, {"uncertain_epsilon": 2.0}),
+        ("mhash", dif.mhash, {"linearize_srgb": True, "channel": "g"}),
+        ("mhash", dif.mhash, {"remove_matte": True, "matte_tolerance": 30}),
+        ("ahash", dif.ahash, {"saliency_crop": True}),
+    ],
+)
+def test_hasher_matches_free_function(image_path, algo, free, options):
+    expected = free(image_path, 8, **options)
+    got = hash_with(algo, image_path, **options)
+    assert got.to_hex() == expected.to_hex()
+    assert got.uncertain == expected.uncertain
+
+
+@pytest.mark.parametrize(
+    "options",
+    [{}, {"keep": 10}, {"center_weight": 0.5}, {"parallel": True, "drop_dc": False}],
+)
+def test_hasher_matches_phash(image_path, options):
+    expected = dif.phash(image_path, 8, 4, **options)
+    assert hash_with("phash", image_path, **options).to_hex() == expected.to_hex()
+
+
+def test_config_getters():
+    config = dif.HashConfig("phash", 16)
+    assert (config.algo, config.hash_size) == ("phash", 16)
+    assert dif.Hasher(config).config.hash_size == 16
+
+
+@pytest.mark.parametrize(
+    "options",
+    [{"snap_to_grid": 1}, {"keep": 0}, {"keep": 65}, {"clahe": (0, 2.0)}],
+)
+def test_config_rejects_invalid_options(options):
+    with pytest.raises(ValueError):
+        dif.HashConfig("phash" if "keep" in options else "dhash", 8, **options)
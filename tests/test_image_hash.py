@@ -0,0 +1,407 @@
+import random
+import struct
+
+import pytest
+
+import dif
+from images import scene, write_png
+
+
+def random_bits(rng, count):
+    return [rng.random() < 0.5 for _ in range(count)]
+
+
+def flip(bits, positions):
+    return [not bit if i in positions else bit for i, bit in enumerate(bits)]
+
+
+@pytest.fixture
+def rng():
+    return random.Random(1234)
+
+
+def test_constructor_packs_bits_lsb_first():
+    bits = [False] * 64
+    bits[0] = bits[9] = bits[63] = True
+    h = dif.ImageHash(bits, 8)
+    assert h.values == [0x01, 0x02, 0, 0, 0, 0, 0, 0x80]
+    assert h.bool_values == bits
+    assert len(h.bool_values) == 64
+
+
+def test_constructor_rejects_wrong_bit_count():
+    with pytest.raises(ValueError):
+        dif.ImageHash([True] * 63, 8)
+
+
+@pytest.mark.parametrize("algo", [dif.ahash, dif.dhash, dif.mhash])
+def test_hashes_agree_with_their_bits(image_path, algo):
+    h = algo(image_path, 8)
+    assert dif.ImageHash(h.bool_values, 8).values == h.values
+
+
+def test_padding_bits_are_zero():
+    h = dif.ImageHash([True] * 25, 5)
+    assert h.values == [0xFF, 0xFF, 0xFF, 0x01]
+    assert h.to_hex() == "ffffff01"
+
+
+def test_packed_len(tmp_path):
+    assert dif.packed_len(8) == 8
+    assert dif.packed_len(10) == 13
+    assert dif.packed_len(5) == dif.ImageHash([True] * 25, 5).byte_len == 4
+    assert dif.ImageHash([False] * 100, 10).byte_len == 13
+    wide = write_png(tmp_path / "wide.png", 64, 36, scene)
+    assert dif.ahash(wide, (16, 9)).byte_len == 18
+
+
+def test_int_round_trip(rng):
+    h = dif.ImageHash(random_bits(rng, 64), 8)
+    value = h.to_int()
+    assert 0 <= value < 1 << 64
+    assert dif.ImageHash.from_int(value, 8).bool_values == h.bool_values
+
+
+def test_int_bit_order():
+    bits = [False] * 64
+    bits[0] = True
+    assert dif.ImageHash(bits, 8).to_int() == 1
+    bits[63] = True
+    assert dif.ImageHash(bits, 8).to_int() == 1 | 1 << 63
+    bits = [False] * 64
+    bits[8] = True
+    h = dif.ImageHash(bits, 8)
+    assert h.to_int() == 0x100
+    assert h.to_int() == int.from_bytes(bytes.fromhex(h.to_hex()), "little")
+
+
+def test_int_distance_is_xor_popcount(rng):
+    a = dif.ImageHash(random_bits(rng, 64), 8)
+    b = dif.ImageHash(random_bits(rng, 64), 8)
+    assert bin(a.to_int() ^ b.to_int()).count("1") == a.distance(b)
+
+
+def test_int_rejects_large_hashes():
+    with pytest.raises(ValueError):
+        dif.ImageHash([False] * 256, 16).to_int()
+    with pytest.raises(ValueError):
+        dif.ImageHash.from_int(1, 16)
+    with pytest.raises(ValueError):
+        dif.ImageHash.from_int(1 << 64, 8)
+
+
+def test_rotate90(image_path, other_path):
+    h = dif.dhash(image_path, 8)
+    bits = h.bool_values
+    assert h.rotate90(4).bool_values == bits
+    assert h.rotate90(0).bool_values == bits
+
+    # Clockwise: the left column read bottom up becomes the top row.
+    rotated = [bits[(7 - x) * 8 + y] for y in range(8) for x in range(8)]
+    assert h.rotate90(1).bool_values == rotated
+    assert h.rotate90(1).values == [
+        sum(bit << i for i, bit in enumerate(rotated[at : at + 8]))
+        for at in range(0, 64, 8)
+    ]
+    other = dif.dhash(other_path, 8)
+    assert other.rotate90(1).distance(h.rotate90(1)) == other.distance(h)
+    assert h.rotate90(1).rotate90(3).bool_values == bits
+
+
+def test_rotate90_rejects_rectangular_hashes(image_path):
+    with pytest.raises(ValueError, match="not square"):
+        dif.ahash(image_path, (8, 4)).rotate90(1)
+
+
+def test_rectangular_grid(tmp_path):
+    path = write_png(tmp_path / "wide.png", 64, 36, scene)
+    h = dif.ahash(path, (16, 9))
+    assert (h.width, h.height) == (16, 9)
+    assert len(h) == 144
+    assert h.distance(dif.ahash(path, (16, 9))) == 0
+    with pytest.raises(ValueError):
+        h.distance(dif.ahash(path, (9, 16)))
+
+
+def test_distance_fast_path_matches_bit_count(rng):
+    for size in (8, 7, 9):
+        a = dif.ImageHash(random_bits(rng, size * size), size)
+        b = dif.ImageHash(random_bits(rng, size * size), size)
+        expected = sum(x != y for x, y in zip(a.bool_values, b.bool_values))
+        assert a.distance(b) == expected
+
+
+def test_distance_rejects_other_sizes():
+    with pytest.raises(ValueError, match="Unmatch size"):
+        dif.ImageHash([False] * 64, 8).distance(dif.ImageHash([False] * 81, 9))
+
+
+def test_distance_hex(rng):
+    a = dif.ImageHash(random_bits(rng, 64), 8)
+    b = dif.ImageHash(random_bits(rng, 64), 8)
+    assert a.distance_hex(b.to_hex()) == a.distance(b)
+    assert a.distance_hex(b.to_hex().upper()) == a.distance(b)
+    with pytest.raises(ValueError):
+        a.distance_hex("00ff")
+    with pytest.raises(ValueError):
+        a.distance_hex("zz" * 8)
+    with pytest.raises(ValueError, match="past the end"):
+        dif.ImageHash([False] * 25, 5).distance_hex("00000002")
+
+
+def test_distance_bounded(rng):
+    bits = random_bits(rng, 64)
+    a = dif.ImageHash(bits, 8)
+    b = dif.ImageHash(flip(bits, {1, 20, 40, 63}), 8)
+    assert a.distance_bounded(b, 4) == 4
+    assert a.distance_bounded(b, 10) == 4
+    assert a.distance_bounded(b, 3) is None
+
+
+def test_masked_distance(rng):
+    bits = random_bits(rng, 64)
+    a = dif.ImageHash(bits, 8)
+    b = dif.ImageHash(flip(bits, {3, 17, 50}), 8)
+    mask = [i not in {3, 17, 50} for i in range(64)]
+    assert a.masked_distance(b, mask) == 0
+    assert a.masked_distance(b, [True] * 64) == 3
+    with pytest.raises(ValueError):
+        a.masked_distance(b, [True] * 63)
+
+
+def test_weighted_distance(rng):
+    bits = random_bits(rng, 16)
+    a = dif.ImageHash(bits, 4)
+    b = dif.ImageHash(flip(bits, {0, 5}), 4)
+    weights = [float(i) for i in range(16)]
+    assert a.weighted_distance(b, weights) == pytest.approx(5.0)
+
+
+def test_distance_either_order_finds_msb_packing(rng):
+    h = dif.ImageHash(random_bits(rng, 64), 8)
+    # The same bytes read most significant bit first.
+    msb = [bool(byte >> (7 - i) & 1) for byte in h.values for i in range(8)]
+    swapped = dif.ImageHash(msb, 8)
+    assert h.distance(swapped) > 0
+    assert h.distance_either_order(swapped) == 0
+    assert h.distance_either_order(h) == 0
+
+
+def test_distance_either_order_ignores_padding():
+    bits = [True] + [False] * 24
+    h = dif.ImageHash(bits, 5)
+    # Reversed, the first byte matches, and the set padding bit of the last one is
+    # left out rather than counted.
+    assert h.distance_either_order(dif.ImageHash(bits[::-1], 5)) == 1
+
+
+def test_min_distance_to(rng):
+    target = dif.ImageHash(random_bits(rng, 64), 8)
+    blocklist = [dif.ImageHash(random_bits(rng, 64), 8) for _ in range(5)]
+    expected = min(target.distance(h) for h in blocklist)
+    assert target.min_distance_to(blocklist) == expected
+    blocklist.insert(2, dif.ImageHash(target.bool_values, 8))
+    assert target.min_distance_to(blocklist) == 0
+    with pytest.raises(ValueError):
+        target.min_distance_to([])
+
+
+def test_distance_histogram(rng):
+    h = dif.ImageHash([False] * 64, 8)
+    gallery = [dif.ImageHash(random_bits(rng, 64), 8) for _ in range(20)]
+    gallery.append(dif.ImageHash([False] * 64, 8))
+    gallery.append(dif.ImageHash([True] * 64, 8))
+    histogram = h.distance_histogram(gallery, 5)
+    assert len(histogram) == 5
+    assert sum(histogram) == len(gallery)
+    assert histogram[0] >= 1 and histogram[-1] >= 1
+    with pytest.raises(ValueError):
+        h.distance_histogram(gallery, 0)
+
+
+def test_downsample_majority_vote():
+    # 4x4 grid whose top left 2x2 block is set, plus one stray bit.
+    bits = [False] * 16
+    for i in (0, 1, 4, 5, 15):
+        bits[i] = True
+    coarse = dif.ImageHash(bits, 4).downsample(2)
+    assert coarse.hash_size == 2
+    assert coarse.bool_values == [True, False, False, False]
+    with pytest.raises(ValueError):
+        dif.ImageHash(bits, 4).downsample(3)
+
+
+def test_downsample_matches_direct_hash(tmp_path):
+    # Quadrants of flat color hash the same at any size.
+    def pixel(x, y):
+        return 230 if (x < 32) != (y < 32) else 20
+
+    path = write_png(tmp_path / "quadrants.png", 64, 64, pixel, mode="L")
+    fine = dif.ahash(path, 8)
+    assert fine.downsample(4).bool_values == dif.ahash(path, 2).bool_values
+
+
+def test_distance_rescaled(image_path, other_path):
+    small = dif.ahash(image_path, 8)
+    large = dif.ahash(image_path, 16)
+    same = small.distance_rescaled(large)
+    assert same == large.distance_rescaled(small)
+    assert same < 0.1
+    assert dif.ahash(other_path, 16).distance_rescaled(small) > same
+    with pytest.raises(ValueError):
+        small.distance_rescaled(dif.ahash(image_path, 12))
+
+
+def test_likely_transformed(rng):
+    h = dif.ImageHash(random_bits(rng, 64), 8)
+    assert h.likely_transformed(h.rotate90(1))
+    assert h.likely_transformed(h.rotate90(3))
+    mirrored = [h.bool_values[y * 8 + 7 - x] for y in range(8) for x in range(8)]
+    assert h.likely_transformed(dif.ImageHash(mirrored, 8))
+    unrelated = dif.ImageHash([i % 3 == 0 for i in range(64)], 8)
+    assert not h.likely_transformed(unrelated, 4)
+
+
+def test_lsh_bucket_keeps_close_hashes_together(tmp_path, image_path, other_path):
+    def speckled(x, y):
+        r, g, b = scene(x, y)
+        return (r, g, b) if (x * 7 + y * 13) % 61 else (255 - r, 255 - g, 255 - b)
+
+    near_path = write_png(tmp_path / "near.png", 64, 48, speckled)
+    base = dif.dhash(image_path, 16)
+    near = dif.dhash(near_path, 16)
+    far = dif.dhash(other_path, 16)
+    assert 0 < base.distance(near) < base.distance(far)
+    assert base.lsh_bucket(8, 7) == base.lsh_bucket(8, 7)
+    near_hits = far_hits = 0
+    for seed in range(200):
+        bucket = base.lsh_bucket(4, seed)
+        near_hits += near.lsh_bucket(4, seed) == bucket
+        far_hits += far.lsh_bucket(4, seed) == bucket
+    assert near_hits > far_hits
+    with pytest.raises(ValueError):
+        base.lsh_bucket(0, 1)
+    with pytest.raises(ValueError):
+        base.lsh_bucket(65, 1)
+
+
+def test_entropy():
+    assert dif.ImageHash([False] * 64, 8).entropy() == 0.0
+    assert dif.ImageHash([True] * 64, 8).entropy() == 0.0
+    balanced = dif.ImageHash([i % 2 == 0 for i in range(64)], 8)
+    assert balanced.entropy() == pytest.approx(1.0)
+    skewed = dif.ImageHash([i < 16 for i in range(64)], 8)
+    assert 0.0 < skewed.entropy() < 1.0
+
+
+def test_compare_is_consistent(rng):
+    a = dif.ImageHash(random_bits(rng, 64), 8)
+    b = dif.ImageHash(random_bits(rng, 64), 8)
+    result = dif.compare(a, b)
+    assert result.are_comparable
+    assert result.distance == a.distance(b)
+    assert result.normalized_distance == pytest.approx(result.distance / 64)
+    assert result.similarity == pytest.approx(1 - result.normalized_distance)
+
+
+def test_compare_other_sizes():
+    result = dif.compare(dif.ImageHash([False] * 64, 8), dif.ImageHash([False] * 4, 2))
+    assert not result.are_comparable
+    assert result.distance is None
+    assert result.normalized_distance is None
+    assert result.similarity is None
+
+
+def test_match_probability(rng):
+    bits = random_bits(rng, 64)
+    h = dif.ImageHash(bits, 8)
+    assert h.match_probability(h) == pytest.approx(1.0, abs=0.01)
+    opposite = dif.ImageHash([not bit for bit in bits], 8)
+    assert h.match_probability(opposite) == pytest.approx(0.0, abs=0.01)
+    scores = [
+        h.match_probability(dif.ImageHash(flip(bits, set(range(n))), 8), "phash")
+        for n in range(0, 65, 4)
+    ]
+    assert scores == sorted(scores, reverse=True)
+    with pytest.raises(ValueError):
+        h.match_probability(h, "nohash")
+
+
+def test_csv_round_trip(tmp_path, image_path):
+    rows = [
+        ("plain.png", dif.ahash(image_path, 8)),
+        ("with, comma.png", dif.dhash(image_path, 8)),
+        ('quote " name.png', dif.phash(image_path, 8, 4)),
+        ("wide.png", dif.ahash(image_path, (16, 9))),
+    ]
+    csv = tmp_path / "hashes.csv"
+    csv.write_text("\n".join(h.to_csv_row(path) for path, h in rows) + "\n")
+    loaded = dif.load_csv(str(csv))
+    assert [path for path, _ in loaded] == [path for path, _ in rows]
+    for (_, h), (_, back) in zip(rows, loaded):
+        assert back.to_hex() == h.to_hex()
+        assert (back.width, back.height) == (h.width, h.height)
+        assert back.algorithm == h.algorithm
+
+
+def test_csv_row_format(image_path):
+    h = dif.ahash(image_path, 8)
+    assert h.to_csv_row("a.png") == f"a.png,ahash,8x8,{h.to_hex()}"
+    assert dif.ImageHash([False] * 4, 2).to_csv_row(None) == ",,2x2,00"
+
+
+def test_csv_rows_with_a_single_size_are_square(tmp_path):
+    csv = tmp_path / "hashes.csv"
+    csv.write_text("path,algorithm,hash_size,hex\na.png,ahash,2,0f\n")
+    [(path, h)] = dif.load_csv(str(csv))
+    assert (path, h.hash_size, h.bool_values) == ("a.png", 2, [True] * 4)
+
+
+def test_save_and_load_hashes(tmp_path, image_path, other_path, rng):
+    hashes = [
+        dif.dhash(rng.choice([image_path, other_path]), size)
+        for size in rng.choices([8, 16, 24], k=300)
+    ]
+    path = str(tmp_path / "gallery.bin")
+    dif.save_hashes(hashes, path)
+    loaded = dif.load_hashes(path)
+    assert len(loaded) == len(hashes)
+    for h, back in zip(hashes, loaded):
+        assert back.hash_size == h.hash_size
+        assert back.values == h.values
+
+
+def test_save_and_load_rectangular_hashes(tmp_path, image_path):
+    hashes = [dif.ahash(image_path, (16, 9)), dif.ahash(image_path, (5, 3))]
+    path = str(tmp_path / "gallery.bin")
+    dif.save_hashes(hashes, path)
+    for h, back in zip(hashes, dif.load_hashes(path)):
+        assert (back.width, back.height) == (h.width, h.height)
+        assert back.bool_values == h.bool_values
+
+
+def test_load_hashes_reads_version_1_files(tmp_path):
+    # One square record: its side, then its packed length and bytes.
+    path = tmp_path / "gallery.bin"
+    path.write_bytes(b"DIFH\x01" + struct.pack("<III", 1, 2, 1) + b"\x0f")
+    [h] = dif.load_hashes(str(path))
+    assert (h.hash_size, h.bool_values) == (2, [True] * 4)
+
+
+@pytest.mark.parametrize(
+    "damage",
+    [
+        lambda data: data[:-1],
+        lambda data: b"XXXX" + data[4:],
+        # A count far past what the file holds.
+        lambda data: data[:5] + b"\xff\xff\xff\x7f" + data[9:],
+        # A width that does not match the record length.
+        lambda data: data[:9] + b"\x09\x00\x00\x00" + data[13:],
+    ],
+)
+def test_load_hashes_rejects_damaged_files(tmp_path, damage):
+    path = tmp_path / "gallery.bin"
+    dif.save_hashes([dif.ImageHash([True] * 64, 8)], str(path))
+    path.write_bytes(damage(path.read_bytes()))
+    with pytest.raises(ValueError):
+        dif.load_hashes(str(path))
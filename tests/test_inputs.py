@@ -0,0 +1,205 @@
+import asyncio
+import base64
+import shutil
+
+import pytest
+
+import dif
+from images import scene, write_png
+
+
+def read(path):
+    with open(path, "rb") as f:
+        return f.read()
+
+
+def hash_file(algo, path):
+    if algo == "phash":
+        return dif.phash(path, 8, 4)
+    return getattr(dif, algo)(path, 8)
+
+
+def feature_error(call):
+    """The message of the error `call` raises when its feature is not built in."""
+    try:
+        call()
+    except ValueError as e:
+        if "not enabled" in str(e):
+            return str(e)
+    return None
+
+
+def test_ahash_async_matches_ahash(image_path, other_path):
+    async def hash_both():
+        return await asyncio.gather(
+            dif.ahash_async(image_path, 8), dif.ahash_async(other_path, 8)
+        )
+
+    a, b = asyncio.run(hash_both())
+    assert a.values == dif.ahash(image_path, 8).values
+    assert b.values == dif.ahash(other_path, 8).values
+
+
+def test_ahash_async_reports_unreadable_files(tmp_path):
+    async def hash_missing():
+        return await dif.ahash_async(str(tmp_path / "missing.png"), 8)
+
+    with pytest.raises(ValueError):
+        asyncio.run(hash_missing())
+
+
+@pytest.mark.parametrize("algo", ["ahash", "dhash", "phash", "mhash"])
+def test_base64_matches_the_file(image_path, algo):
+    expected = hash_file(algo, image_path)
+    encoded = base64.b64encode(read(image_path)).decode()
+    from_base64 = getattr(dif, f"{algo}_base64")
+    assert from_base64(encoded, 8).values == expected.values
+    data_url = "data:image/png;base64," + encoded
+    assert from_base64(data_url, 8).values == expected.values
+
+
+def test_base64_accepts_url_safe_unpadded_input(image_path):
+    encoded = base64.urlsafe_b64encode(read(image_path)).decode().rstrip("=")
+    expected = dif.ahash(image_path, 8).values
+    assert dif.ahash_base64(encoded, 8).values == expected
+
+
+def test_base64_rejects_invalid_input(image_path):
+    with pytest.raises(ValueError, match="Invalid base64"):
+        dif.ahash_base64("not base64!", 8)
+    with pytest.raises(ValueError, match="not base64 encoded"):
+        dif.ahash_base64("data:image/png,abc", 8)
+    with pytest.raises(ValueError):
+        dif.ahash_base64(base64.b64encode(b"not an image").decode(), 8)
+
+
+@pytest.mark.parametrize("algo", ["ahash", "dhash", "phash", "mhash"])
+def test_raw_bytes_are_the_packed_hash(image_path, algo):
+    expected = hash_file(algo, image_path)
+    raw = getattr(dif, f"{algo}_raw_bytes")(image_path, 8)
+    assert isinstance(raw, bytes)
+    assert list(raw) == expected.values
+
+
+def test_ahash_from_grayscale_buffer():
+    pixels = bytes((x * 37 + y * 11) % 256 for y in range(8) for x in range(8))
+    mean = sum(pixels) / len(pixels)
+    h = dif.ahash_from_grayscale_buffer(pixels, 8)
+    assert h.bool_values == [v >= mean for v in pixels]
+    with pytest.raises(ValueError, match="size \\* size"):
+        dif.ahash_from_grayscale_buffer(pixels[:-1], 8)
+    with pytest.raises(ValueError):
+        dif.ahash_from_grayscale_buffer(b"", 0)
+
+
+def test_ahash_from_matrix_matches_the_file(tmp_path):
+    def level(x, y):
+        return (x * 5 + y * 9) % 256
+
+    path = write_png(tmp_path / "matrix.png", 30, 20, level, "L")
+    matrix = [[level(x, y) for x in range(30)] for y in range(20)]
+    assert dif.ahash_from_matrix(matrix, 8).values == dif.ahash(path, 8).values
+
+
+def test_ahash_from_matrix_needs_equal_rows():
+    with pytest.raises(ValueError, match="rows of equal length"):
+        dif.ahash_from_matrix([[1, 2, 3], [4, 5]], 8)
+    with pytest.raises(ValueError):
+        dif.ahash_from_matrix([], 8)
+
+
+def test_ahash_from_ndarray(image_path):
+    np = pytest.importorskip("numpy")
+    rgb = np.array(
+        [[scene(x, y) for x in range(64)] for y in range(48)], dtype=np.uint8
+    )
+    expected = dif.ahash(image_path, 8).values
+    assert dif.ahash_from_ndarray(rgb, 8, False).values == expected
+    bgr = np.ascontiguousarray(rgb[:, :, ::-1])
+    assert dif.ahash_from_ndarray(bgr, 8, True).values == expected
+    with pytest.raises(ValueError, match="shape"):
+        dif.ahash_from_ndarray(rgb[:, :, :2].copy(), 8, False)
+
+
+def test_tiled_hasher_matches_the_whole_image(tmp_path, image_path):
+    hasher = dif.TiledHasher(64, 48)
+    for ty in (0, 24):
+        for tx in (0, 32):
+            tile = write_png(
+                tmp_path / f"tile_{tx}_{ty}.png",
+                32,
+                24,
+                lambda x, y: scene(x + tx, y + ty),
+            )
+            hasher.feed(read(tile), tx, ty)
+    for algo, expected in [
+        ("ahash", dif.ahash(image_path, 8)),
+        ("dhash", dif.dhash(image_path, 8)),
+    ]:
+        assert hasher.finalize(8, algo).values == expected.values
+
+
+def test_tiled_hasher_downsamples_large_images(tmp_path):
+    path = write_png(tmp_path / "large.png", 200, 100, scene)
+    hasher = dif.TiledHasher(200, 100, 50)
+    hasher.feed(read(path), 0, 0)
+    assert hasher.finalize(8, "ahash").distance(dif.ahash(path, 8)) <= 4
+
+
+def test_tiled_hasher_validates_tiles(tmp_path, image_path):
+    hasher = dif.TiledHasher(64, 48)
+    with pytest.raises(ValueError, match="outside of the image"):
+        hasher.feed(read(image_path), 8, 0)
+    with pytest.raises(ValueError, match="Cannot open tile"):
+        hasher.feed(b"not an image", 0, 0)
+    tile = write_png(tmp_path / "tile.png", 32, 48, scene)
+    hasher.feed(read(tile), 0, 0)
+    with pytest.raises(ValueError, match="not fully covered"):
+        hasher.finalize(8, "ahash")
+    with pytest.raises(ValueError):
+        dif.TiledHasher(0, 48)
+
+
+def test_hash_cached_reuses_the_stored_hash(tmp_path, image_path):
+    cache = str(tmp_path / "hashes.csv")
+    if feature_error(lambda: dif.hash_cached(image_path, 8, "dhash", cache)):
+        pytest.skip("built without the `cache` feature")
+
+    expected = dif.dhash(image_path, 8).to_hex()
+    assert dif.hash_cached(image_path, 8, "dhash", cache).to_hex() == expected
+    rows = open(cache).read().splitlines()
+    assert len(rows) == 1
+    assert rows[0].endswith(",dhash,8," + expected)
+
+    # A stored hash is returned as is while the file is unchanged.
+    fake = "ff" * 8
+    with open(cache, "w") as f:
+        f.write(rows[0][: -len(expected)] + fake + "\n")
+    assert dif.hash_cached(image_path, 8, "dhash", cache).to_hex() == fake
+
+    # Rewriting the file invalidates its entry.
+    write_png(image_path, 64, 48, lambda x, y: scene(x, y, 16))
+    rehashed = dif.hash_cached(image_path, 8, "dhash", cache).to_hex()
+    assert rehashed == dif.dhash(image_path, 8).to_hex()
+    assert len(open(cache).read().splitlines()) == 1
+
+
+def test_hash_cached_names_the_missing_feature(tmp_path, image_path):
+    cache = str(tmp_path / "hashes.csv")
+    message = feature_error(lambda: dif.hash_cached(image_path, 8, "dhash", cache))
+    if message is None:
+        pytest.skip("built with the `cache` feature")
+    assert "`cache` feature" in message
+
+
+def test_hash_video_frame(tmp_path, image_path):
+    video = str(tmp_path / "missing.mp4")
+    message = feature_error(lambda: dif.hash_video_frame(video, 8, "ahash", 0.0))
+    if message is not None:
+        assert "`video` feature" in message
+        pytest.skip("built without the `video` feature")
+    if shutil.which("ffmpeg") is None:
+        pytest.skip("ffmpeg is not installed")
+    # A still image is a single frame video to ffmpeg.
+    h = dif.hash_video_frame(image_path, 8, "ahash", 0.0)
+    assert h.distance(dif.ahash(image_path, 8)) <= 4
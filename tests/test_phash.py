@@ -0,0 +1,223 @@
+import math
+
+import pytest
+
+import dif
+from images import scene, write_png
+
+
+def fft_enabled(path):
+    try:
+        dif.phash(path, 8, 4, backend="fft")
+    except ValueError as e:
+        if "not enabled" in str(e):
+            return False
+    return True
+
+
+@pytest.fixture
+def requires_fft(image_path):
+    if not fft_enabled(image_path):
+        pytest.skip("built without the `fft` feature")
+
+
+def texture(x, y, phase=0):
+    """A tiling with a period of 32 pixels, shifted by `phase` pixels."""
+    u, v = (x + phase) % 32, (y + phase) % 32
+    return 220 if u < 6 and v < 10 else 60 if u > 11 else 140
+
+
+def test_drop_dc_ignores_overall_brightness(tmp_path):
+    # The same content at two brightness levels, far enough from black and white that
+    # the resize filter's overshoot is never clipped.
+    def bright(x, y):
+        return tuple(c // 2 + 90 for c in scene(x, y))
+
+    def dark(x, y):
+        return tuple(c // 2 + 20 for c in scene(x, y))
+
+    a = write_png(tmp_path / "bright.png", 64, 48, bright)
+    b = write_png(tmp_path / "dark.png", 64, 48, dark)
+    dropped = dif.phash(a, 8, 4, drop_dc=True)
+    assert dropped.values == dif.phash(b, 8, 4, drop_dc=True).values
+    assert dropped.values == dif.phash(a, 8, 4).values
+    assert dropped.bool_values[0] is False
+    assert dif.phash(a, 8, 4, drop_dc=False).bool_values[0] is True
+
+
+def test_phash_takes_a_square_block(image_path):
+    assert len(dif.phash(image_path, 8, 4).bool_values) == 64
+    assert len(dif.phash(image_path, 16, 4).bool_values) == 256
+
+
+def test_backends_agree(image_path):
+    expected = dif.phash(image_path, 8, 4).values
+    assert dif.phash(image_path, 8, 4, backend="separable").values == expected
+    assert dif.phash(image_path, 8, 4, backend="naive").values == expected
+
+
+def test_fft_backend_agrees(image_path, requires_fft):
+    expected = dif.phash(image_path, 8, 4).values
+    assert dif.phash(image_path, 8, 4, backend="fft").values == expected
+
+
+def near_flat(level):
+    """A flat image at `level`, and one with its right half a single level off."""
+    step = 1 if level < 255 else -1
+    return [lambda x, y: level, lambda x, y: level + step * (x >= 32)]
+
+
+@pytest.mark.parametrize("level", [37, 128, 200, 255])
+def test_backends_agree_on_flat_images(tmp_path, level):
+    for i, fill in enumerate(near_flat(level)):
+        path = write_png(tmp_path / f"{i}.png", 64, 48, fill, "L")
+        expected = dif.phash(path, 8, 4, backend="naive").values
+        assert dif.phash(path, 8, 4, backend="separable").values == expected
+        if fft_enabled(path):
+            assert dif.phash(path, 8, 4, backend="fft").values == expected
+
+
+def test_flat_image_sets_no_bits(tmp_path):
+    path = write_png(tmp_path / "flat.png", 64, 48, lambda x, y: 128, "L")
+    assert not any(dif.phash(path, 8, 4).bool_values)
+
+
+def test_unknown_backend(image_path):
+    with pytest.raises(ValueError, match="Unknown backend"):
+        dif.phash(image_path, 8, 4, backend="wavelet")
+
+
+def test_parallel_dct_is_bit_identical(tmp_path):
+    path = write_png(tmp_path / "large.png", 300, 260, scene)
+    serial = dif.phash(path, 32, 8)
+    assert dif.phash(path, 32, 8, parallel=True).values == serial.values
+
+
+def test_center_weight_discounts_corners(tmp_path):
+    def corners(x, y, level):
+        near_corner = min(x, 63 - x) < 14 and min(y, 63 - y) < 14
+        return level if near_corner else 30 + (x * 3 + y * 2) % 180
+
+    a = write_png(tmp_path / "a.png", 64, 64, lambda x, y: corners(x, y, 250), "L")
+    b = write_png(tmp_path / "b.png", 64, 64, lambda x, y: corners(x, y, 0), "L")
+    plain = dif.phash(a, 8, 4).distance(dif.phash(b, 8, 4))
+    weighted = dif.phash(a, 8, 4, center_weight=0.9).distance(
+        dif.phash(b, 8, 4, center_weight=0.9)
+    )
+    assert weighted < plain
+
+
+def test_zero_center_weight_changes_nothing(image_path):
+    expected = dif.phash(image_path, 8, 4).values
+    assert dif.phash(image_path, 8, 4, center_weight=0.0).values == expected
+
+
+@pytest.mark.parametrize("keep", [1, 10, 63, 64])
+def test_keep_sets_the_bit_count(image_path, keep):
+    h = dif.phash(image_path, 8, 4, keep=keep)
+    assert len(h.bool_values) == keep
+    assert (h.width, h.height) == (keep, 1)
+
+
+def test_keep_follows_zigzag_order(image_path):
+    # Keeping every coefficient gives the same bits, reordered along the
+    # anti-diagonals, alternating direction.
+    order = [
+        (s - i, i) if s % 2 == 0 else (i, s - i)
+        for s in range(15)
+        for i in range(max(0, s - 7), min(s, 7) + 1)
+    ]
+    full = dif.phash(image_path, 8, 4).bool_values
+    zigzag = dif.phash(image_path, 8, 4, keep=64).bool_values
+    assert zigzag == [full[v * 8 + u] for v, u in order]
+
+
+@pytest.mark.parametrize("keep", [0, 65])
+def test_keep_range(image_path, keep):
+    with pytest.raises(ValueError, match="Keep must be between"):
+        dif.phash(image_path, 8, 4, keep=keep)
+
+
+def test_keep_hash_round_trips_through_files(tmp_path, image_path):
+    h = dif.phash(image_path, 8, 4, keep=10)
+    csv = tmp_path / "hashes.csv"
+    csv.write_text(h.to_csv_row("a.png") + "\n")
+    [(_, from_csv)] = dif.load_csv(str(csv))
+    path = str(tmp_path / "gallery.bin")
+    dif.save_hashes([h], path)
+    [from_binary] = dif.load_hashes(path)
+    for back in (from_csv, from_binary):
+        assert (back.width, back.height) == (10, 1)
+        assert back.bool_values == h.bool_values
+    assert from_csv.algorithm == "phash"
+
+
+def test_aligned_phash_undoes_a_shift(tmp_path):
+    def smooth(x, y):
+        r, g, b = scene(x // 2, y // 2)
+        return (r + g + b) // 3
+
+    original = write_png(tmp_path / "original.png", 128, 96, smooth, "L")
+    shifted = write_png(
+        tmp_path / "shifted.png", 128, 96, lambda x, y: smooth(x + 6, y + 4), "L"
+    )
+    plain = dif.phash(original, 8, 4).distance(dif.phash(shifted, 8, 4))
+    aligned = dif.aligned_phash(original, shifted, 8, 4)
+    assert aligned <= 4
+    assert aligned < plain
+    assert dif.aligned_phash(original, original, 8, 4) == 0
+
+
+def test_phash_spectral_ignores_the_texture_phase(tmp_path, requires_fft):
+    a = write_png(tmp_path / "a.png", 64, 64, texture, "L")
+    b = write_png(tmp_path / "b.png", 64, 64, lambda x, y: texture(x, y, 8), "L")
+    spectral = dif.phash_spectral(a, 8).distance(dif.phash_spectral(b, 8))
+    plain = dif.phash(a, 8, 4).distance(dif.phash(b, 8, 4))
+    assert spectral < plain
+    assert spectral <= 2
+
+
+def test_phash_spectral_is_a_phash(image_path, requires_fft):
+    h = dif.phash_spectral(image_path, 8)
+    assert h.algorithm == "phash"
+    assert (h.width, h.height) == (8, 8)
+
+
+def test_phash_spectral_rejects_size_zero(image_path):
+    with pytest.raises(ValueError, match="Hash size must be at least 1"):
+        dif.phash_spectral(image_path, 0)
+
+
+def test_phash_spectral_names_the_missing_feature(image_path):
+    if fft_enabled(image_path):
+        pytest.skip("built with the `fft` feature")
+    with pytest.raises(ValueError, match="`fft` feature"):
+        dif.phash_spectral(image_path, 8)
+
+
+def test_text_robust_hash_tolerates_captions(tmp_path):
+    def photo(x, y):
+        return int(128 + 70 * math.sin(x / 11) * math.cos(y / 13))
+
+    def captioned(x, y):
+        # Thin white strokes of lettering boxed across the middle.
+        if y in (64, 95) or 64 <= y < 96 and x % 6 == 0:
+            return 255
+        return photo(x, y)
+
+    base = write_png(tmp_path / "base.png", 128, 128, photo, "L")
+    caption = write_png(tmp_path / "caption.png", 128, 128, captioned, "L")
+    robust = dif.text_robust_hash(base, 8).distance(dif.text_robust_hash(caption, 8))
+    plain = dif.phash(base, 8, 4).distance(dif.phash(caption, 8, 4))
+    assert robust < plain
+
+
+def test_large_ahash_is_the_mean_threshold(tmp_path):
+    # 64x64 is hashed without resizing, and summed on several threads.
+    def level(x, y):
+        return (x * 7 + y * 13 + (x * y) % 17) % 256
+
+    path = write_png(tmp_path / "large.png", 64, 64, level, "L")
+    levels = [level(x, y) for y in range(64) for x in range(64)]
+    mean = sum(levels) / len(levels)
+    assert dif.ahash(path, 64).bool_values == [v >= mean for v in levels]
@@ -0,0 +1,15 @@
+import dif
+
+
+def test_positions_cover_every_bit_but_dc(image_path):
+    h, positions = dif.phash_debug(image_path, 8, 4)
+    assert len(positions) == len(h.bool_values) - 1
+    assert (0, 0) not in positions
+    assert len(set(positions)) == len(positions)
+    assert positions[:3] == [(0, 1), (0, 2), (0, 3)]
+
+
+def test_dc_bit_is_unset(image_path):
+    h, _ = dif.phash_debug(image_path, 8, 4)
+    assert not h.bool_values[0]
+    assert h.to_hex() == dif.phash(image_path, 8, 4).to_hex()
@@ -0,0 +1,37 @@
+import time
+
+import dif
+from images import scene, write_png
+
+
+def test_filter_sets_the_dct_input_resize(image_path):
+    default = dif.phash(image_path, 8, 4)
+    assert dif.phash(image_path, 8, 4, filter="lanczos3").to_hex() == default.to_hex()
+    triangle = dif.phash(image_path, 8, 4, filter="triangle")
+    assert triangle.distance(default) <= 2
+
+
+def test_intermediate_filter_takes_precedence(image_path):
+    default = dif.phash(image_path, 8, 4)
+    h = dif.phash(image_path, 8, 4, filter="nearest", intermediate_filter="lanczos3")
+    assert h.to_hex() == default.to_hex()
+
+
+def test_triangle_intermediate_filter_is_faster_and_close(tmp_path):
+    path = write_png(
+        tmp_path / "large.png", 640, 480, lambda x, y: scene(x // 10, y // 10)
+    )
+
+    def timed(**kwargs):
+        best = None
+        for _ in range(3):
+            start = time.perf_counter()
+            h = dif.phash(path, 8, 4, **kwargs)
+            elapsed = time.perf_counter() - start
+            best = elapsed if best is None else min(best, elapsed)
+        return best, h
+
+    lanczos_time, lanczos = timed()
+    triangle_time, triangle = timed(intermediate_filter="triangle")
+    assert triangle_time < lanczos_time
+    assert triangle.distance(lanczos) <= 2
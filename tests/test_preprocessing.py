@@ -0,0 +1,412 @@
+import math
+import zlib
+
+import pytest
+
+import dif
+from images import scene, write_png, write_tiff
+
+
+def scaled(pixel, factor, offset=0):
+    """`pixel` with every channel scaled by `factor` and shifted by `offset`."""
+
+    def scaled_pixel(x, y):
+        return tuple(min(255, max(0, round(c * factor + offset))) for c in pixel(x, y))
+
+    return scaled_pixel
+
+
+def landscape(x, y):
+    return (40, 60, 80) if x < 16 or x >= 80 else scene(x - 16, y)
+
+
+def test_background_is_used_under_transparency(tmp_path):
+    def pixel(x, y):
+        r, g, b = scene(x, y)
+        return (r, g, b, 255 if x < 32 else 0)
+
+    path = write_png(tmp_path / "half.png", 64, 48, pixel, mode="RGBA")
+    white = dif.ahash(path, 8)
+    assert white.values == dif.ahash(path, 8, background=(255, 255, 255)).values
+    black = dif.ahash(path, 8, background=(0, 0, 0))
+    assert white.distance(black) > 8
+
+
+def test_background_does_not_change_opaque_images(image_path):
+    expected = dif.dhash(image_path, 8).values
+    assert dif.dhash(image_path, 8, background=(0, 0, 0)).values == expected
+
+
+def test_center_is_invariant_to_a_brightness_offset(tmp_path):
+    base = scaled(scene, 0.7)
+    dark = write_png(tmp_path / "dark.png", 64, 48, base)
+    bright = write_png(tmp_path / "bright.png", 64, 48, scaled(base, 1.0, 40))
+    a = dif.ahash(dark, 8, center=True)
+    assert a.values == dif.ahash(bright, 8, center=True).values
+    assert a.values == dif.ahash(dark, 8).values
+
+
+@pytest.mark.parametrize(
+    "hash_file, default",
+    [
+        (dif.ahash, "triangle"),
+        (dif.mhash, "triangle"),
+        (dif.dhash, "lanczos3"),
+        (lambda path, size, **kw: dif.phash(path, size, 4, **kw), "lanczos3"),
+    ],
+)
+def test_default_filters(image_path, hash_file, default):
+    expected = hash_file(image_path, 8).values
+    assert hash_file(image_path, 8, filter=default).values == expected
+    distinct = {
+        tuple(hash_file(image_path, 16, filter=name).values)
+        for name in ("nearest", "triangle", "catmullrom", "gaussian", "lanczos3")
+    }
+    assert len(distinct) > 1
+
+
+def test_unknown_filter(image_path):
+    with pytest.raises(ValueError, match="Unknown filter"):
+        dif.ahash(image_path, 8, filter="bicubic")
+
+
+def test_equalize_reduces_exposure_differences(tmp_path):
+    under = write_png(tmp_path / "under.png", 64, 48, scaled(scene, 0.25))
+    well = write_png(tmp_path / "well.png", 64, 48, scene)
+    plain = dif.dhash(under, 8).distance(dif.dhash(well, 8))
+    equalized = dif.dhash(under, 8, equalize=True).distance(
+        dif.dhash(well, 8, equalize=True)
+    )
+    assert equalized < plain
+
+
+def test_crop_to_square(tmp_path):
+    def center(x, y):
+        return landscape(x + 24, y)
+
+    wide = write_png(tmp_path / "wide.png", 96, 48, landscape)
+    square = write_png(tmp_path / "square.png", 48, 48, center)
+    cropped = dif.dhash(wide, 8, crop_to_square=True)
+    assert cropped.values == dif.dhash(square, 8, crop_to_square=True).values
+    assert dif.dhash(wide, 8).distance(dif.dhash(square, 8)) > cropped.distance(
+        dif.dhash(square, 8)
+    )
+
+
+def test_blur_resists_salt_and_pepper_noise(tmp_path):
+    def smooth(x, y):
+        return (x * 4 + y * 2) % 256 if (x // 16 + y // 16) % 2 else 255 - x * 2
+
+    def noisy(x, y):
+        spot = (x * 7919 + y * 104729) % 5
+        return 0 if spot == 0 else 255 if spot == 1 else smooth(x, y)
+
+    clean = write_png(tmp_path / "clean.png", 64, 64, smooth, mode="L")
+    noise = write_png(tmp_path / "noisy.png", 64, 64, noisy, mode="L")
+    plain = dif.dhash(clean, 16).distance(dif.dhash(noise, 16))
+    blurred = dif.dhash(clean, 16, blur=2.0).distance(dif.dhash(noise, 16, blur=2.0))
+    assert blurred < plain
+
+
+def test_palette_png_matches_rgb(tmp_path):
+    palette = [(0, 0, 0), (250, 20, 20), (20, 200, 40), (30, 60, 230), (255, 255, 255)]
+
+    def index(x, y):
+        return (x // 8 + y // 6 * 3) % len(palette)
+
+    indexed = write_png(tmp_path / "palette.png", 64, 48, index, palette=palette)
+    rgb = write_png(tmp_path / "rgb.png", 64, 48, lambda x, y: palette[index(x, y)])
+    for hash_file in (dif.ahash, dif.dhash, dif.mhash):
+        assert hash_file(indexed, 8).values == hash_file(rgb, 8).values
+
+
+def test_cmyk_tiff_matches_rgb(tmp_path):
+    def cmyk(x, y):
+        return ((x * 4) % 256, (y * 5) % 256, 255 - x * 3, 40 if x < 32 else 0)
+
+    def rgb(x, y):
+        c, m, y_, k = cmyk(x, y)
+        return tuple(round((255 - v) * (255 - k) / 255) for v in (c, m, y_))
+
+    samples = bytes(v for y in range(48) for x in range(64) for v in cmyk(x, y))
+    tiff = write_tiff(tmp_path / "cmyk.tif", [(64, 48, 5, samples)])
+    png = write_png(tmp_path / "rgb.png", 64, 48, rgb)
+    for hash_file in (dif.ahash, dif.dhash, dif.mhash):
+        assert hash_file(tiff, 8).values == hash_file(png, 8).values
+
+
+def test_pages_of_a_tiff(tmp_path):
+    def page(pixel):
+        samples = bytes(v for y in range(48) for x in range(64) for v in pixel(x, y))
+        return (64, 48, 2, samples)
+
+    path = write_tiff(
+        tmp_path / "pages.tif",
+        [page(scene), page(lambda x, y: (255 - x * 4, y * 5, 128))],
+    )
+    first = dif.ahash(path, 8, page=0)
+    assert first.values == dif.ahash(path, 8).values
+    assert first.distance(dif.ahash(path, 8, page=1)) > 0
+    with pytest.raises(ValueError, match="out of range"):
+        dif.ahash(path, 8, page=2)
+
+
+def test_single_page_formats_only_have_page_zero(image_path):
+    dif.dhash(image_path, 8, page=0)
+    with pytest.raises(ValueError, match="out of range"):
+        dif.dhash(image_path, 8, page=1)
+
+
+def test_presized_image_is_hashed_as_is(tmp_path):
+    levels = [(x * 37 + y * 91) % 256 for y in range(8) for x in range(8)]
+    path = write_png(tmp_path / "thumb.png", 8, 8, lambda x, y: levels[y * 8 + x], "L")
+    mean = sum(levels) / len(levels)
+    for name in ("nearest", "lanczos3"):
+        h = dif.ahash(path, 8, filter=name)
+        assert h.bool_values == [level >= mean for level in levels]
+
+
+def test_strict_rejects_a_truncated_jpeg(tmp_path, jpeg_path):
+    with open(jpeg_path, "rb") as f:
+        data = f.read()
+    truncated = tmp_path / "truncated.jpg"
+    truncated.write_bytes(data[: len(data) * 2 // 3])
+
+    assert dif.ahash(jpeg_path, 8, strict=True).distance(dif.ahash(jpeg_path, 8)) == 0
+    with pytest.raises(ValueError):
+        dif.ahash(str(truncated), 8, strict=True)
+    best_effort = dif.ahash(str(truncated), 8)
+    assert len(best_effort.bool_values) == 64
+
+
+def test_linearize_srgb_moves_mid_tone_boundaries(tmp_path):
+    def gradient(x, y):
+        return (x * 4, (x * 4 + y * 2) % 256, 255 - x * 4)
+
+    path = write_png(tmp_path / "gradient.png", 64, 32, gradient)
+    linear = dif.ahash(path, 8, linearize_srgb=True)
+    assert linear.values != dif.ahash(path, 8).values
+
+
+def test_linearize_srgb_keeps_gray_images(gray_path, gray_rgb_path):
+    a = dif.ahash(gray_path, 8, linearize_srgb=True)
+    assert a.values == dif.ahash(gray_rgb_path, 8, linearize_srgb=True).values
+
+
+@pytest.mark.parametrize("hash_file", [dif.ahash, dif.dhash, dif.mhash])
+def test_gray_and_gray_rgb_hash_the_same(gray_path, gray_rgb_path, hash_file):
+    assert hash_file(gray_path, 8).values == hash_file(gray_rgb_path, 8).values
+
+
+def test_unpremultiply(tmp_path):
+    def straight(x, y):
+        r, g, b = scene(x, y)
+        return (r, g, b, 64 + (x * 3) % 192)
+
+    def premultiplied(x, y):
+        r, g, b, a = straight(x, y)
+        return tuple(round(c * a / 255) for c in (r, g, b)) + (a,)
+
+    straight_path = write_png(tmp_path / "straight.png", 64, 48, straight, "RGBA")
+    premul_path = write_png(tmp_path / "premul.png", 64, 48, premultiplied, "RGBA")
+    expected = dif.ahash(straight_path, 8)
+    assert dif.ahash(premul_path, 8, unpremultiply=True).distance(expected) <= 1
+    assert dif.ahash(premul_path, 8).distance(expected) > 1
+
+
+def test_uncertain_bits_of_a_low_contrast_image(tmp_path):
+    def faint(x, y):
+        return 120 + (x // 8 + y // 8) % 3
+
+    path = write_png(tmp_path / "faint.png", 64, 64, faint, mode="L")
+    other = write_png(tmp_path / "moved.png", 64, 64, lambda x, y: faint(x + 8, y), "L")
+    a = dif.ahash(path, 8, uncertain_epsilon=1.5)
+    b = dif.ahash(other, 8, uncertain_epsilon=1.5)
+    assert any(a.uncertain)
+    assert not any(dif.ahash(path, 8).uncertain)
+    assert a.certain_distance(b) < a.distance(b)
+
+
+def test_snap_to_grid_quantizes_levels(tmp_path):
+    path = write_png(tmp_path / "ramp.png", 90, 80, lambda x, y: (x * 3 + y) % 256, "L")
+    snapped = dif.dhash(path, 8, snap_to_grid=2)
+    assert snapped.values != dif.dhash(path, 8).values
+    with pytest.raises(ValueError):
+        dif.dhash(path, 8, snap_to_grid=1)
+
+
+def test_snap_to_grid_steadies_rescaled_screenshots(tmp_path):
+    def ui(x, y, scale):
+        u, v = x / scale, y / scale
+        if 4 <= v < 12:
+            return 40
+        if 16 <= v < 60 and 8 <= u < 40:
+            return 230 if (int(v) - 16) % 8 < 5 else 200
+        return 250
+
+    one = write_png(tmp_path / "ui1.png", 64, 64, lambda x, y: ui(x, y, 1.0), "L")
+    other = write_png(tmp_path / "ui2.png", 80, 80, lambda x, y: ui(x, y, 1.25), "L")
+    plain = dif.dhash(one, 8).distance(dif.dhash(other, 8))
+    snapped = dif.dhash(one, 8, snap_to_grid=4).distance(
+        dif.dhash(other, 8, snap_to_grid=4)
+    )
+    assert snapped < plain
+
+
+def test_channel_selects_a_single_channel(tmp_path):
+    def red_only(x, y):
+        return (255 if (x // 8 + y // 8) % 2 else 0, 100 + x, 50)
+
+    path = write_png(tmp_path / "red.png", 64, 64, red_only)
+    r = dif.ahash(path, 8, channel="r")
+    g = dif.ahash(path, 8, channel="g")
+    assert r.distance(g) > 16
+    assert dif.ahash(path, 8, channel="luma").values == dif.ahash(path, 8).values
+    with pytest.raises(ValueError, match="Unknown channel"):
+        dif.ahash(path, 8, channel="alpha")
+
+
+def test_clahe_reduces_lighting_differences(tmp_path):
+    def lit(x, y):
+        waves = 60 * math.sin(x / 7) * math.cos(y / 9) + 50 * math.sin((x + y) / 13)
+        level = 128 + waves
+        return max(0, min(255, int(level)))
+
+    def low_light(x, y):
+        # Darker, with crushed shadows and light falling off to the left.
+        return int(80 * (lit(x, y) / 255) ** 2 * (0.3 + 0.7 * x / 64))
+
+    normal = write_png(tmp_path / "normal.png", 64, 64, lit, mode="L")
+    low = write_png(tmp_path / "low.png", 64, 64, low_light, mode="L")
+    plain = dif.dhash(normal, 8).distance(dif.dhash(low, 8))
+    clahe = dif.dhash(normal, 8, clahe=(8, 3.0)).distance(
+        dif.dhash(low, 8, clahe=(8, 3.0))
+    )
+    assert clahe < plain
+
+
+@pytest.mark.parametrize("clahe", [(0, 2.0), (4, 0.0), (4, -1.0)])
+def test_clahe_rejects_invalid_parameters(image_path, clahe):
+    with pytest.raises(ValueError):
+        dif.ahash(image_path, 8, clahe=clahe)
+
+
+def test_icc_profiles_are_ignored(tmp_path, image_path):
+    profile = b"sRGB\x00\x00" + zlib.compress(bytes(range(200)))
+    tagged = write_png(tmp_path / "icc.png", 64, 48, scene, chunks=[(b"iCCP", profile)])
+    for hash_file in (dif.ahash, dif.dhash, dif.mhash):
+        expected = hash_file(image_path, 8).values
+        assert hash_file(tagged, 8).values == expected
+    assert dif.phash(tagged, 8, 4).values == dif.phash(image_path, 8, 4).values
+
+
+def test_min_entropy(tmp_path, image_path):
+    solid = write_png(tmp_path / "solid.png", 32, 32, lambda x, y: (90, 90, 90))
+    with pytest.raises(ValueError, match="too uniform"):
+        dif.dhash(solid, 8, min_entropy=0.5)
+    with pytest.raises(ValueError, match="too uniform"):
+        dif.ahash(solid, 8, min_entropy=0.5)
+    assert dif.dhash(image_path, 8, min_entropy=0.5).entropy() >= 0.5
+
+
+def test_profile_times_the_phases(image_path):
+    h, timings = dif.phash(image_path, 8, 4, profile=True)
+    assert h.values == dif.phash(image_path, 8, 4).values
+    for ms in (timings.decode_ms, timings.resize_ms, timings.transform_ms):
+        assert ms >= 0.0
+    assert timings.decode_ms + timings.resize_ms + timings.transform_ms < 10_000
+    assert isinstance(dif.ahash(image_path, 8, profile=False), dif.ImageHash)
+
+
+def document(angle):
+    """A page of text-like bars, rotated by `angle` degrees around its center."""
+    bars = [
+        (12 + (row * 37 + word * 23) % 9 + word * 18, 14 + row * 12)
+        for row in range(10)
+        for word in range(5)
+    ]
+    t = math.radians(angle)
+    cos, sin = math.cos(t), math.sin(t)
+
+    def pixel(x, y):
+        dx, dy = x - 60, y - 80
+        u, v = 60 + dx * cos + dy * sin, 80 - dx * sin + dy * cos
+        for left, top in bars:
+            if left <= u < left + 14 and top <= v < top + 5:
+                return 20
+        return 245
+
+    return pixel
+
+
+def test_auto_deskew_straightens_a_tilted_scan(tmp_path):
+    straight = write_png(tmp_path / "straight.png", 120, 160, document(0), "L")
+    tilted = write_png(tmp_path / "tilted.png", 120, 160, document(3), "L")
+    plain = dif.dhash(straight, 8).distance(dif.dhash(tilted, 8))
+    deskewed = dif.dhash(straight, 8, auto_deskew=True).distance(
+        dif.dhash(tilted, 8, auto_deskew=True)
+    )
+    assert deskewed < plain
+
+
+def test_pad_modes(tmp_path):
+    path = write_png(tmp_path / "wide.png", 64, 40, scene)
+    hashes = {
+        mode: dif.ahash(path, 8, pad_mode=mode).values
+        for mode in ("resize", "reflect", "edge")
+    }
+    assert hashes["resize"] == dif.ahash(path, 8).values
+    assert hashes["reflect"] != hashes["resize"]
+    assert hashes["edge"] != hashes["resize"]
+    with pytest.raises(ValueError, match="Unknown pad mode"):
+        dif.ahash(path, 8, pad_mode="wrap")
+
+
+def test_remove_matte(tmp_path):
+    def matted(border, noise):
+        def pixel(x, y):
+            if border <= x < border + 64 and border <= y < border + 48:
+                return scene(x - border, y - border)
+            level = 250 - (x * 13 + y * 7) % noise
+            return (level, level, level)
+
+        return pixel
+
+    thin = write_png(tmp_path / "thin.png", 72, 56, matted(4, 3))
+    thick = write_png(tmp_path / "thick.png", 104, 88, matted(20, 5))
+    plain = dif.dhash(thin, 8).distance(dif.dhash(thick, 8))
+    removed = dif.dhash(thin, 8, remove_matte=True).distance(
+        dif.dhash(thick, 8, remove_matte=True)
+    )
+    assert removed < plain
+    assert removed <= 4
+
+
+def test_saliency_crop(tmp_path):
+    def subject(x, y):
+        if (x - 12) ** 2 + (y - 12) ** 2 < 100:
+            return (230, 40, 40)
+        return (40, 40, 200) if x < 12 else (250, 220, 30)
+
+    def composite(background, left, top):
+        def pixel(x, y):
+            if left <= x < left + 24 and top <= y < top + 24:
+                return subject(x - left, y - top)
+            return background(x, y)
+
+        return pixel
+
+    calm = write_png(
+        tmp_path / "calm.png", 96, 96, composite(lambda x, y: (128, 128, 128), 20, 30)
+    )
+    busy = write_png(
+        tmp_path / "busy.png",
+        96,
+        96,
+        composite(lambda x, y: (100 + x // 3, 120, 140 - y // 3), 56, 10),
+    )
+    plain = dif.dhash(calm, 8).distance(dif.dhash(busy, 8))
+    cropped = dif.dhash(calm, 8, saliency_crop=True).distance(
+        dif.dhash(busy, 8, saliency_crop=True)
+    )
+    assert cropped < plain
@@ -0,0 +1,63 @@
+import pytest
+
+import dif
+
+
+def test_quad_hash_reports_cell_grid(image_path):
+    h = dif.quad_hash(image_path, 8)
+    assert h.algorithm == "quad"
+    assert h.hash_size == 8
+    assert (h.width, h.height) == (16, 8)
+
+
+def test_quad_distance(image_path, other_path):
+    a = dif.quad_hash(image_path, 8)
+    assert dif.quad_distance(a, dif.quad_hash(image_path, 8)) == 0
+    assert dif.quad_distance(a, dif.quad_hash(other_path, 8)) > 0
+
+
+def test_quad_distance_rejects_other_algorithms(image_path):
+    with pytest.raises(ValueError, match="Not a quad hash"):
+        dif.quad_distance(dif.ahash(image_path, 8), dif.ahash(image_path, 8))
+
+
+def test_quad_hash_rejects_tiny_size(image_path):
+    with pytest.raises(ValueError):
+        dif.quad_hash(image_path, 1)
+
+
+def test_quad_hash_round_trips_through_files(tmp_path, image_path, other_path):
+    h = dif.quad_hash(image_path, 8)
+    csv = tmp_path / "hashes.csv"
+    csv.write_text(h.to_csv_row("a.png") + "\n")
+    [(_, from_csv)] = dif.load_csv(str(csv))
+    path = str(tmp_path / "gallery.bin")
+    dif.save_hashes([h], path)
+    [from_binary] = dif.load_hashes(path)
+    other = dif.quad_hash(other_path, 8)
+    for back in (from_csv, from_binary):
+        assert back.algorithm == "quad"
+        assert (back.hash_size, back.width, back.height) == (8, 16, 8)
+        assert back.bool_values == h.bool_values
+        assert dif.quad_distance(back, other) == dif.quad_distance(h, other)
+
+
+def test_hash_cached_reuses_a_stored_quad_hash(tmp_path, image_path):
+    cache = str(tmp_path / "hashes.csv")
+    try:
+        h = dif.hash_cached(image_path, 8, "quad", cache)
+    except ValueError as e:
+        if "not enabled" not in str(e):
+            raise
+        pytest.skip("built without the `cache` feature")
+    assert h.to_hex() == dif.quad_hash(image_path, 8).to_hex()
+
+    # A hit returns the stored hash and leaves the file alone.
+    [row] = open(cache).read().splitlines()
+    fake = "ff" * 16
+    with open(cache, "w") as f:
+        f.write(row[: -len(h.to_hex())] + fake + "\n")
+    cached = dif.hash_cached(image_path, 8, "quad", cache)
+    assert cached.to_hex() == fake
+    assert (cached.algorithm, cached.width, cached.height) == ("quad", 16, 8)
+    assert open(cache).read().splitlines() == [row[: -len(h.to_hex())] + fake]
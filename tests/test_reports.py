@@ -0,0 +1,105 @@
+import struct
+import zlib
+
+import pytest
+
+import dif
+from images import write_png
+
+
+ALGORITHMS = {"ahash", "dhash", "phash", "mhash"}
+
+
+def read_png(path):
+    """`(width, height, rows of RGB tuples)` of an 8-bit RGB PNG."""
+    with open(path, "rb") as f:
+        data = f.read()
+    width, height, depth, color_type = struct.unpack(">IIBB", data[16:26])
+    assert (depth, color_type) == (8, 2)
+    idat = b""
+    at = 8
+    while at < len(data):
+        (length,) = struct.unpack(">I", data[at : at + 4])
+        if data[at + 4 : at + 8] == b"IDAT":
+            idat += data[at + 8 : at + 8 + length]
+        at += 12 + length
+
+    raw = zlib.decompress(idat)
+    stride = width * 3
+    rows, previous = [], bytearray(stride)
+    for y in range(height):
+        kind = raw[y * (stride + 1)]
+        line = bytearray(raw[y * (stride + 1) + 1 : (y + 1) * (stride + 1)])
+        for i in range(stride):
+            left = line[i - 3] if i >= 3 else 0
+            up = previous[i]
+            up_left = previous[i - 3] if i >= 3 else 0
+            if kind == 1:
+                line[i] = (line[i] + left) & 0xFF
+            elif kind == 2:
+                line[i] = (line[i] + up) & 0xFF
+            elif kind == 3:
+                line[i] = (line[i] + (left + up) // 2) & 0xFF
+            elif kind == 4:
+                p = left + up - up_left
+                pa, pb, pc = abs(p - left), abs(p - up), abs(p - up_left)
+                pred = left if pa <= pb and pa <= pc else up if pb <= pc else up_left
+                line[i] = (line[i] + pred) & 0xFF
+        rows.append([tuple(line[x * 3 : x * 3 + 3]) for x in range(width)])
+        previous = line
+    return width, height, rows
+
+
+def test_render_diff(tmp_path):
+    # The right half is set in `a`, everything but the top left quadrant in `b`.
+    a = dif.ahash(
+        write_png(tmp_path / "a.png", 64, 64, lambda x, y: 255 * (x >= 32), "L"), 8
+    )
+    b = dif.ahash(
+        write_png(
+            tmp_path / "b.png", 64, 64, lambda x, y: 255 * (x >= 32 or y >= 32), "L"
+        ),
+        8,
+    )
+    assert a.bool_values == [x >= 4 for y in range(8) for x in range(8)]
+    assert b.bool_values == [x >= 4 or y >= 4 for y in range(8) for x in range(8)]
+    out = str(tmp_path / "diff.png")
+    dif.render_diff(a, b, out, 3)
+
+    width, height, rows = read_png(out)
+    assert (width, height) == ((2 * 8 + 1) * 3, 8 * 3)
+    black, white, red, gap = (0, 0, 0), (255, 255, 255), (255, 0, 0), (128, 128, 128)
+    # Three pixels per cell: a, the blank separator, then b.
+    top, bottom = ([rows[y][x] for x in (0, 12, 24, 27, 39)] for y in (0, 21))
+    assert top == [black, white, gap, black, white]
+    assert bottom == [red, white, gap, red, white]
+
+
+def test_render_diff_validates_its_input(tmp_path, image_path):
+    out = str(tmp_path / "diff.png")
+    h = dif.ahash(image_path, 8)
+    with pytest.raises(ValueError):
+        dif.render_diff(h, dif.ahash(image_path, 4), out, 4)
+    with pytest.raises(ValueError, match="Scale"):
+        dif.render_diff(h, h, out, 0)
+
+
+def test_robustness_report(image_path, jpeg_path):
+    report = dif.robustness_report(image_path, 8)
+    assert set(report) == ALGORITHMS
+    assert all(0 <= distance <= 64 for distance in report.values())
+    assert set(dif.robustness_report(jpeg_path, 8)) == ALGORITHMS
+
+
+def test_bit_error_profile(image_path):
+    profile = dif.bit_error_profile(image_path, 8, "dhash")
+    assert len(profile) == 64
+    # Each bit is flipped by some number of the seven perturbations.
+    assert all(0.0 <= p <= 1.0 for p in profile)
+    assert all(p * 7 == pytest.approx(round(p * 7)) for p in profile)
+    assert dif.bit_error_profile(image_path, 8, "dhash") == profile
+
+
+def test_bit_error_profile_of_a_flat_image_is_stable(tmp_path):
+    flat = write_png(tmp_path / "flat.png", 32, 32, lambda x, y: 128, "L")
+    assert dif.bit_error_profile(flat, 8, "ahash") == [0.0] * 64
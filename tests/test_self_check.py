@@ -0,0 +1,20 @@
+import dif
+
+# Digests of the built-in test image with the `image` 0.24 codecs and resizing. A change
+# here means hashes drifted, so stored hashes from older builds no longer compare.
+EXPECTED = {
+    "ahash": "00303070f0ffffff",
+    "dhash": "1064e4e464601000",
+    "phash": "d8d8b6f6febebfff",
+    "mhash": "0030307070fcffff",
+}
+
+
+def test_self_check_digests():
+    lines = dif.self_check().splitlines()
+    digests = dict(line.split(":") for line in lines)
+    assert digests == EXPECTED
+
+
+def test_self_check_is_deterministic():
+    assert dif.self_check() == dif.self_check()
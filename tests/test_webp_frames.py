@@ -0,0 +1,100 @@
+import struct
+
+import pytest
+
+import dif
+
+
+class BitWriter:
+    def __init__(self):
+        self.bits = []
+
+    def write(self, value, count):
+        self.bits.extend((value >> i) & 1 for i in range(count))
+
+    def to_bytes(self):
+        return bytes(
+            sum(bit << j for j, bit in enumerate(self.bits[i : i + 8]))
+            for i in range(0, len(self.bits), 8)
+        )
+
+
+def vp8l(width, height, lit):
+    """Lossless bitstream of a black and green image, green where `lit(x, y)`."""
+    b = BitWriter()
+    b.write(0x2F, 8)
+    b.write(width - 1, 14)
+    b.write(height - 1, 14)
+    b.write(0, 1)
+    b.write(0, 3)
+    b.write(0, 1)  # no transform
+    b.write(0, 1)  # no color cache
+    b.write(0, 1)  # no meta prefix codes
+    # Green: a simple code of the two symbols 0 and 255.
+    b.write(1, 1)
+    b.write(1, 1)
+    b.write(1, 1)
+    b.write(0, 8)
+    b.write(255, 8)
+    # Red, blue and alpha: a single symbol each.
+    for symbol in (0, 0, 255):
+        b.write(1, 1)
+        b.write(0, 1)
+        b.write(1, 1)
+        b.write(symbol, 8)
+    # Distance: a single symbol.
+    b.write(1, 1)
+    b.write(0, 1)
+    b.write(0, 1)
+    b.write(0, 1)
+    for y in range(height):
+        for x in range(width):
+            b.write(1 if lit(x, y) else 0, 1)
+    return b.to_bytes()
+
+
+def chunk(kind, data):
+    out = kind + struct.pack("<I", len(data)) + data
+    return out + b"\0" if len(data) % 2 else out
+
+
+def u24(value):
+    return struct.pack("<I", value)[:3]
+
+
+def write_animated_webp(path, width, height, frames):
+    """Writes an animation with one full-size frame per `(kind, bitstream)`."""
+    canvas = bytes([0x02, 0, 0, 0]) + u24(width - 1) + u24(height - 1)
+    body = b"WEBP" + chunk(b"VP8X", canvas)
+    body += chunk(b"ANIM", b"\0" * 6)
+    for kind, bitstream in frames:
+        frame = u24(0) + u24(0) + u24(width - 1) + u24(height - 1) + u24(100) + b"\x02"
+        body += chunk(b"ANMF", frame + chunk(kind, bitstream))
+    with open(path, "wb") as f:
+        f.write(b"RIFF" + struct.pack("<I", len(body)) + body)
+    return str(path)
+
+
+def test_animated_webp_hashes_every_frame(tmp_path):
+    patterns = [
+        lambda x, y: x < 16,
+        lambda x, y: y < 16,
+        lambda x, y: (x + y) % 16 < 8,
+    ]
+    path = write_animated_webp(
+        tmp_path / "anim.webp", 32, 32, [(b"VP8L", vp8l(32, 32, p)) for p in patterns]
+    )
+    hashes = dif.hash_frames(path, 8, "ahash")
+    assert len(hashes) == 3
+    assert hashes[0].distance(hashes[1]) > 0
+
+
+def test_unsupported_webp_feature_is_named(tmp_path):
+    # A lossy frame predicted from the previous one, which the decoder cannot handle.
+    tag = 1 | 1 << 4 | 10 << 5
+    interframe = struct.pack("<I", tag)[:3] + b"\0" * 64
+    path = write_animated_webp(
+        tmp_path / "inter.webp", 8, 8, [(b"VP8 ", interframe), (b"VP8 ", interframe)]
+    )
+    with pytest.raises(ValueError, match="Non-keyframe frames"):
+        dif.hash_frames(path, 8, "ahash")